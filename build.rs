@@ -0,0 +1,14 @@
+//! Generate the Rust types for `proto/fetch_sac.proto`
+//!
+
+fn main() -> std::io::Result<()> {
+    // Use a vendored `protoc` (and its well-known types) so nothing needs to be installed
+    //
+    let protoc = protoc_bin_vendored::protoc_bin_path().unwrap();
+    let wkt = protoc_bin_vendored::include_path().unwrap();
+
+    println!("cargo:rerun-if-changed=proto/fetch_sac.proto");
+    prost_build::Config::new()
+        .protoc_executable(protoc)
+        .compile_protos(&["proto/fetch_sac.proto"], &["proto".into(), wkt])
+}
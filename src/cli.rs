@@ -1,6 +1,10 @@
 use std::path::PathBuf;
+use std::str::FromStr;
 
-use clap::{crate_authors, crate_name, crate_version, Parser};
+use anyhow::{anyhow, Result};
+use clap::{crate_authors, crate_name, crate_version, Parser, Subcommand, ValueEnum};
+
+use fetch_sac::{Column, CsvOptions, Order, Quoting, SchemaKind, ScrapeOptions};
 
 pub const ABOUT: &str = "Fetch the latest SAC codes data from ECTL.\n\
 Source: https://www.eurocontrol.int/asterix/";
@@ -11,14 +15,44 @@ Source: https://www.eurocontrol.int/asterix/";
 #[clap(name = crate_name ! (), about = ABOUT)]
 #[clap(version = crate_version ! (), author = crate_authors ! ())]
 pub struct Opts {
-    /// CSV
-    #[clap(short = 'C', long)]
+    /// CSV, same as `-F csv`
+    #[clap(short = 'C', long, conflicts_with = "format")]
     pub csv: bool,
-    /// JSON
-    #[clap(short = 'J', long)]
+    /// JSON, same as `-F json`
+    #[clap(short = 'J', long, conflicts_with_all = ["csv", "format"])]
     pub json: bool,
-    /// Output file.
-    #[clap(short = 'o', long)]
+    /// Output format.
+    #[clap(short = 'F', long, value_enum)]
+    pub format: Option<Format>,
+    /// CSV field delimiter, a single character or `tab`.
+    #[clap(long, default_value = ",", value_parser = parse_delimiter, help_heading = "CSV")]
+    pub delimiter: u8,
+    /// CSV quoting: always, necessary, non-numeric or never.
+    #[clap(long, default_value = "always", help_heading = "CSV")]
+    pub quote: Quoting,
+    /// No CSV header line.
+    #[clap(long, help_heading = "CSV")]
+    pub no_header: bool,
+    /// CSV columns among region, slug, sac, row, decimal, binary, lo, hi and label (default region,sac,label), the header names for categories, --sic and inspect (default all).
+    #[clap(long, value_delimiter = ',', help_heading = "CSV")]
+    pub columns: Option<Vec<String>>,
+    /// Read a file saved earlier instead of fetching the page (.csv, .tsv, .json, .pb, .msgpack, .cbor, .bincode, .html for categories and mirror).
+    #[clap(short = 'i', long)]
+    pub input: Option<PathBuf>,
+    /// Order of the codes in every area: page, hex or label.
+    #[clap(long, default_value = "page")]
+    pub order: Order,
+    /// Keep the text of the page verbatim (entities, whitespace), for auditing.
+    #[clap(long)]
+    pub raw: bool,
+    /// Local SIC registry (.toml, .yaml, .csv), checked against the SAC codes and merged with them.
+    #[clap(long)]
+    pub sic: Option<PathBuf>,
+    /// Use the snapshot bundled with fetch-sac instead of fetching the page.
+    #[clap(short = 'b', long, conflicts_with = "input")]
+    pub bundled: bool,
+    /// Output file (mandatory for `-F sqlite`).
+    #[clap(short = 'o', long, required_if_eq("format", "sqlite"))]
     pub output: Option<PathBuf>,
     /// Quiet mode
     #[clap(short = 'q', long)]
@@ -29,4 +63,185 @@ pub struct Opts {
     /// Display utility full version.
     #[clap(short = 'V', long)]
     pub version: bool,
+    /// Subcommands, default is to fetch the SAC codes
+    #[clap(subcommand)]
+    pub subcmd: Option<SubCommand>,
+}
+
+/// Things we can do besides fetching the SAC codes
+#[derive(Debug, Subcommand)]
+pub enum SubCommand {
+    /// Print the JSON Schema of one of the structured outputs
+    Schema {
+        /// Which output
+        #[clap(value_enum, default_value_t = SchemaArg::Envelope)]
+        kind: SchemaArg,
+    },
+    /// Generate source code with a static table of SAC codes
+    Codegen {
+        /// Which language
+        #[clap(value_enum)]
+        lang: Lang,
+    },
+    /// Fetch the ASTERIX categories and their current edition instead of the SAC codes
+    ///
+    /// `-i` takes either a saved copy of the page or JSON generated earlier.
+    Categories,
+    /// Download the specifications linked from the categories into a local directory
+    ///
+    /// A `manifest.json` with the category, edition, URL, SHA-256 and size of every document is
+    /// kept there, unchanged documents are not downloaded again.
+    Mirror {
+        /// Where to put the documents
+        #[clap(default_value = "specs")]
+        dir: PathBuf,
+    },
+    /// Count the records per source in a raw ASTERIX recording, labelled from the SAC list
+    ///
+    /// The recording is a plain sequence of data blocks, without any framing.  Records are
+    /// walked for CAT001, 002, 021, 034, 048 and 062, only the first one of every block is read
+    /// for the other categories.
+    Inspect {
+        /// Recording to read
+        file: PathBuf,
+    },
+}
+
+/// Languages we can generate code for
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum Lang {
+    /// Rust module with `SACS`, `RANGES` and `lookup()`
+    Rust,
+    /// C header with `enum sac_code`, `sac_table` and `sac_name()`
+    C,
+    /// Python module with `SACS`, `RANGES` and `lookup()`
+    Python,
+    /// Lua `value_string`-style table for Wireshark dissectors
+    WiresharkLua,
+    /// C `value_string` array for Wireshark dissectors
+    WiresharkC,
+}
+
+/// Outputs with a JSON Schema, see `SchemaKind`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum SchemaArg {
+    /// List of areas (`-F json`)
+    Areas,
+    /// One area
+    Area,
+    /// One SAC code
+    Sac,
+    /// One record of `-F ndjson`
+    Record,
+    /// `-F json-envelope`
+    Envelope,
+    /// `-F json-envelope categories`
+    Categories,
+    /// `-F json-envelope --sic`
+    Sites,
+}
+
+impl From<SchemaArg> for SchemaKind {
+    fn from(value: SchemaArg) -> Self {
+        match value {
+            SchemaArg::Areas => SchemaKind::Areas,
+            SchemaArg::Area => SchemaKind::Area,
+            SchemaArg::Sac => SchemaKind::Sac,
+            SchemaArg::Record => SchemaKind::Record,
+            SchemaArg::Envelope => SchemaKind::Envelope,
+            SchemaArg::Categories => SchemaKind::Categories,
+            SchemaArg::Sites => SchemaKind::Sites,
+        }
+    }
+}
+
+impl Opts {
+    /// CSV dialect, with the default columns
+    ///
+    pub fn csv_dialect(&self) -> CsvOptions {
+        CsvOptions {
+            delimiter: self.delimiter,
+            quoting: self.quote,
+            header: !self.no_header,
+            ..CsvOptions::default()
+        }
+    }
+
+    /// CSV dialect and columns of the SAC codes
+    ///
+    pub fn csv_options(&self) -> Result<CsvOptions> {
+        let mut opts = self.csv_dialect();
+        if let Some(columns) = &self.columns {
+            opts.columns = columns
+                .iter()
+                .map(|c| Column::from_str(c).map_err(|e| anyhow!(e)))
+                .collect::<Result<Vec<_>>>()?;
+        }
+        Ok(opts)
+    }
+
+    /// How to scrape the page
+    ///
+    pub fn scrape_options(&self) -> ScrapeOptions {
+        ScrapeOptions { raw: self.raw }
+    }
+
+    /// Output format, taking the `-C` and `-J` shortcuts into account
+    ///
+    pub fn format(&self) -> Format {
+        match self.format {
+            Some(fmt) => fmt,
+            None if self.csv => Format::Csv,
+            None if self.json => Format::Json,
+            None => Format::Text,
+        }
+    }
+}
+
+/// Delimiter is either one ASCII character or `tab`
+///
+fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        _ if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => Err(format!("invalid delimiter {}", s)),
+    }
+}
+
+/// All the output formats we support
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Plain text
+    #[default]
+    Text,
+    /// CSV
+    Csv,
+    /// JSON, compact
+    Json,
+    /// JSON, pretty-printed with metadata
+    JsonEnvelope,
+    /// JSON Lines, one record per code
+    Ndjson,
+    /// XML, see `schemas/fetch-sac.xsd`
+    Xml,
+    /// Markdown, one table per area
+    Markdown,
+    /// Standalone HTML report
+    Html,
+    /// PostgreSQL script with idempotent inserts
+    Sql,
+    /// SQLite database, a new snapshot is added on every run
+    Sqlite,
+    /// MessagePack
+    Msgpack,
+    /// CBOR
+    Cbor,
+    /// bincode, only readable from Rust
+    Bincode,
+    /// Protobuf `Registry` message, see `proto/fetch_sac.proto`
+    Protobuf,
+    /// Excel workbook, one sheet per area
+    Xlsx,
+    /// OpenDocument spreadsheet, one sheet per area
+    Ods,
 }
@@ -1,22 +1,58 @@
 //! Module to deal with an Area as a collection, as an iterator, etc.
 //!
 
-use std::collections::btree_map::{IntoValues, Iter, Keys, Values, ValuesMut};
-use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
 use std::ops::{Index, IndexMut};
+use std::str::FromStr;
 
+use indexmap::map::{IntoValues, Iter, Keys, Values, ValuesMut};
+use indexmap::IndexMap;
 use log::trace;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::core::SAC;
+
+/// How to order the codes of an area
+///
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Order {
+    /// As published on the page
+    #[default]
+    Page,
+    /// By value, ranges with their lower bound
+    Hex,
+    /// By label, case-insensitive
+    Label,
+}
+
+impl FromStr for Order {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "page" => Ok(Order::Page),
+            "hex" => Ok(Order::Hex),
+            "label" => Ok(Order::Label),
+            _ => Err(format!("unknown order {}", s)),
+        }
+    }
+}
+
 /// One `Area` (group of countries, continent, etc.)
 ///
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Area {
     /// Name of the area
     label: String,
-    /// List of codes
-    list: BTreeMap<String, String>,
+    /// List of codes, in the current order
+    list: IndexMap<String, String>,
+    /// Codes in the order of the page
+    #[serde(default)]
+    rows: Vec<String>,
+    /// Notes and footnotes published with the table
+    #[serde(default)]
+    notes: Vec<String>,
 }
 
 impl Area {
@@ -25,7 +61,9 @@ impl Area {
     pub fn new(s: &str) -> Self {
         Area {
             label: s.to_owned(),
-            list: BTreeMap::new(),
+            list: IndexMap::new(),
+            rows: vec![],
+            notes: vec![],
         }
     }
 
@@ -36,7 +74,18 @@ impl Area {
         self.label.to_owned()
     }
 
-    /// Wrap `BtreeMap::get`
+    /// Name of the area usable as an identifier, "Asia & Pacific" gives "asia-pacific"
+    ///
+    pub fn slug(&self) -> String {
+        self.label
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    /// Wrap `IndexMap::get`
     ///
     #[inline]
     pub fn get(&self, name: &str) -> Option<&String> {
@@ -106,13 +155,74 @@ impl Area {
         self.list.iter()
     }
 
-    /// Add a code
+    /// Add a code, a new one goes after all the others
     ///
     pub fn add(&mut self, code: &str, label: &str) -> &mut Self {
         trace!("add({}, {})", code, label.to_owned());
+        if !self.list.contains_key(code) {
+            self.rows.push(code.to_owned());
+        }
         self.list.insert(code.into(), label.to_owned());
         self
     }
+
+    /// Add a note about the area
+    ///
+    pub fn add_note(&mut self, note: &str) -> &mut Self {
+        self.notes.push(note.to_owned());
+        self
+    }
+
+    /// Notes about the area, in page order
+    ///
+    #[inline]
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+
+    /// Row of the code in its table on the page, starting at 1
+    ///
+    /// Data saved without the page order gives the position in the file.
+    ///
+    pub fn row(&self, code: &str) -> Option<usize> {
+        let idx = if self.rows.is_empty() {
+            self.list.get_index_of(code)
+        } else {
+            self.rows.iter().position(|c| c == code)
+        };
+        idx.map(|i| i + 1)
+    }
+
+    /// Set the page order, codes are listed in page order with the unknown ones at the end
+    ///
+    pub(crate) fn set_rows(&mut self, rows: &[String]) {
+        let mut codes: Vec<_> = self.list.keys().cloned().collect();
+        codes.sort_by_key(|code| rows.iter().position(|c| c == code).unwrap_or(usize::MAX));
+        self.rows = codes;
+    }
+
+    /// Reorder the codes, `Order::Page` goes back to the page order
+    ///
+    pub fn sort(&mut self, order: Order) {
+        match order {
+            Order::Page => {
+                let rows = self.rows.clone();
+                self.list
+                    .sort_by_cached_key(|code, _| rows.iter().position(|c| c == code));
+            }
+            Order::Hex => self.list.sort_by_cached_key(|code, _| {
+                let v = match SAC::try_from(code.as_str()).unwrap_or_default() {
+                    SAC::Hex(s) => usize::from_str_radix(&s, 16).ok(),
+                    SAC::Range { lo, .. } => Some(lo),
+                    SAC::Empty => None,
+                };
+                (v.is_none(), v, code.to_owned())
+            }),
+            Order::Label => self
+                .list
+                .sort_by_cached_key(|code, label| (label.to_lowercase(), code.to_owned())),
+        }
+    }
 }
 
 impl<'a> IntoIterator for &'a Area {
@@ -143,9 +253,8 @@ impl IndexMut<&str> for Area {
     ///
     #[inline]
     fn index_mut(&mut self, s: &str) -> &mut Self::Output {
-        let me = self.list.get_mut(s);
-        if me.is_none() {
-            self.list.insert(s.to_owned(), "".to_owned());
+        if !self.list.contains_key(s) {
+            self.add(s, "");
         }
         self.list.get_mut(s).unwrap()
     }
@@ -166,13 +275,16 @@ impl Display for Area {
 
 #[cfg(test)]
 mod tests {
+    use rstest::rstest;
+
     use super::*;
 
     #[test]
     fn test_area_new() {
         let a = Area::new("foo");
         assert_eq!("foo", a.label);
-        assert_eq!(BTreeMap::new(), a.list);
+        assert!(a.list.is_empty());
+        assert!(a.rows.is_empty());
     }
 
     #[test]
@@ -181,7 +293,77 @@ mod tests {
 
         a.add("666", "Hell");
         assert_eq!("foo", a.label);
-        assert!(a.list.get("666").is_some());
+        assert!(a.list.contains_key("666"));
         assert_eq!("Hell", a.list.get("666").unwrap());
     }
+
+    fn sample() -> Area {
+        let mut a = Area::new("foo");
+        a.add("A0...C3", "reserved")
+            .add("0A", "Zulu")
+            .add("94", "alpha")
+            .add("01", "Mike");
+        a
+    }
+
+    fn codes(a: &Area) -> Vec<&str> {
+        a.keys().map(|k| k.as_str()).collect()
+    }
+
+    #[test]
+    fn test_area_page_order() {
+        let mut a = sample();
+        assert_eq!(vec!["A0...C3", "0A", "94", "01"], codes(&a));
+        assert_eq!(Some(1), a.row("A0...C3"));
+        assert_eq!(Some(4), a.row("01"));
+        assert_eq!(None, a.row("FF"));
+
+        // Updating a code keeps its place
+        //
+        a.add("0A", "Yankee");
+        assert_eq!(vec!["A0...C3", "0A", "94", "01"], codes(&a));
+        assert_eq!(Some(2), a.row("0A"));
+    }
+
+    #[rstest]
+    #[case(Order::Hex, vec!["01", "0A", "94", "A0...C3"])]
+    #[case(Order::Label, vec!["94", "01", "A0...C3", "0A"])]
+    #[case(Order::Page, vec!["A0...C3", "0A", "94", "01"])]
+    fn test_area_sort(#[case] order: Order, #[case] res: Vec<&str>) {
+        let mut a = sample();
+        a.sort(Order::Hex);
+        a.sort(order);
+        assert_eq!(res, codes(&a));
+        assert_eq!(Some(1), a.row("A0...C3"));
+    }
+
+    #[test]
+    fn test_area_rows_serde() {
+        let mut a = sample();
+        a.sort(Order::Hex);
+        let mut b: Area = serde_json::from_str(&serde_json::to_string(&a).unwrap()).unwrap();
+        assert_eq!(a, b);
+        b.sort(Order::Page);
+        assert_eq!(vec!["A0...C3", "0A", "94", "01"], codes(&b));
+
+        // Older files without `rows`
+        //
+        let b: Area =
+            serde_json::from_str(r#"{"label":"foo","list":{"94":"a","01":"b"}}"#).unwrap();
+        assert_eq!(Some(2), b.row("01"));
+    }
+
+    #[test]
+    fn test_area_notes() {
+        let mut a = Area::new("foo");
+        assert!(a.notes().is_empty());
+        a.add_note("Reserved for tests").add_note("See also bar");
+        assert_eq!(vec!["Reserved for tests", "See also bar"], a.notes());
+    }
+
+    #[test]
+    fn test_area_slug() {
+        assert_eq!("eu-region", Area::new("EU Region").slug());
+        assert_eq!("asia-pacific", Area::new(" Asia & Pacific").slug());
+    }
 }
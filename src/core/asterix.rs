@@ -0,0 +1,627 @@
+//! Module walking raw ASTERIX recordings to find which sources they contain
+//!
+//! A recording is a sequence of data blocks: CAT (1 byte), LEN (2 bytes, including these 3) and
+//! records.  Each record starts with its FSPEC, FRN 1 being I0xx/010, the Data Source
+//! Identifier, in all the usual categories.
+//!
+//! Records inside a block are not delimited, so we need the length of every item to go to the
+//! next one.  This is only known for the categories whose UAP is described below (001, 002,
+//! 034, 048, 062 and 021), for the others only the first record of every block is read.
+//!
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use anyhow::{anyhow, Result};
+use log::{debug, trace};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{lookup_sac, Area, DataSourceId, Tabular};
+
+/// How to get the length of a data item
+///
+#[derive(Clone, Copy, Debug)]
+enum Item {
+    /// Fixed length
+    Fixed(usize),
+    /// Parts of that length, the last bit of each part telling whether another follows
+    Variable(usize),
+    /// One byte with the repetition factor, then as many parts of that length
+    Repetitive(usize),
+    /// First byte is the length of the whole item
+    Explicit,
+    /// Variable primary subfield, then the subfields present, in order
+    Compound(&'static [Item]),
+    /// Not used in this UAP
+    Spare,
+    /// Random Field Sequencing, not supported
+    Rfs,
+}
+
+use Item::{Compound as C, Explicit as E, Fixed as F, Repetitive as R, Spare as S, Variable as V};
+
+#[rustfmt::skip]
+const CAT001_PLOT: &[Item] = &[
+    F(2), V(1), F(4), F(2), F(2), V(1), F(2),
+    F(2), F(1), F(1), F(2), F(4), F(2), V(1),
+    F(1), S, S, S, S, E, Item::Rfs,
+];
+
+#[rustfmt::skip]
+const CAT001_TRACK: &[Item] = &[
+    F(2), V(1), F(2), F(4), F(4), F(4), F(2),
+    F(2), F(2), V(1), F(1), F(1), V(1), V(1),
+    F(2), F(2), F(4), F(2), V(1), E, Item::Rfs,
+];
+
+#[rustfmt::skip]
+const CAT002: &[Item] = &[
+    F(2), F(1), F(1), F(3), F(2), V(1), V(1),
+    R(2), F(8), F(2), V(1), S, E, Item::Rfs,
+];
+
+const CAT034_050: &[Item] = &[F(1), S, S, F(1), F(1), F(2), S];
+const CAT034_060: &[Item] = &[F(1), S, S, F(1), F(1), F(1), S];
+#[rustfmt::skip]
+const CAT034: &[Item] = &[
+    F(2), F(1), F(3), F(1), F(2), C(CAT034_050), C(CAT034_060),
+    R(2), F(8), F(1), F(8), F(2), E, E,
+];
+
+const CAT048_130: &[Item] = &[F(1); 7];
+const CAT048_120: &[Item] = &[F(2), R(6), S, S, S, S, S];
+#[rustfmt::skip]
+const CAT048: &[Item] = &[
+    F(2), F(3), V(1), F(4), F(2), F(2), C(CAT048_130),
+    F(3), F(6), R(8), F(2), F(4), F(4), V(1),
+    F(4), V(1), F(2), F(4), F(2), C(CAT048_120), F(2),
+    F(7), F(1), F(2), F(1), F(2), E, E,
+];
+
+#[rustfmt::skip]
+const CAT062_380: &[Item] = &[
+    F(3), F(6), F(2), F(2), F(2), F(2), F(2),
+    V(1), R(15), F(2), F(2), F(7), F(2), F(2),
+    F(2), F(2), F(2), F(2), F(1), F(8), F(1),
+    F(6), F(2), F(1), R(8), F(2), F(2), F(2),
+];
+const CAT062_290: &[Item] = &[F(1), F(1), F(1), F(1), F(2), F(1), F(1), F(1), F(1), F(1)];
+const CAT062_295: &[Item] = &[F(1); 31];
+#[rustfmt::skip]
+const CAT062_390: &[Item] = &[
+    F(2), F(7), F(4), F(1), F(4), F(1), F(4),
+    F(4), F(3), F(2), F(2), R(4), F(6), F(1),
+    F(7), F(7), F(2), F(7),
+];
+const CAT062_110: &[Item] = &[F(1), F(4), F(6), F(2), F(2), F(1), F(1)];
+const CAT062_500: &[Item] = &[F(4), F(2), F(4), F(1), F(1), F(2), F(2), F(1)];
+const CAT062_340: &[Item] = &[F(2), F(4), F(2), F(2), F(2), F(1)];
+#[rustfmt::skip]
+const CAT062: &[Item] = &[
+    F(2), S, F(1), F(3), F(8), F(6), F(4),
+    F(2), F(2), F(7), C(CAT062_380), F(2), V(1), C(CAT062_290),
+    F(1), C(CAT062_295), F(2), F(2), F(2), F(2), C(CAT062_390),
+    V(1), F(1), C(CAT062_110), F(2), V(3), C(CAT062_500), C(CAT062_340),
+    S, S, S, S, S, E, E,
+];
+
+const CAT021_220: &[Item] = &[F(2), F(2), F(2), F(1), S, S, S];
+const CAT021_110: &[Item] = &[V(1), R(15), S, S, S, S, S];
+const CAT021_295: &[Item] = &[F(1); 23];
+#[rustfmt::skip]
+const CAT021: &[Item] = &[
+    F(2), V(1), F(2), F(1), F(3), F(6), F(8),
+    F(3), F(2), F(2), F(3), F(3), F(4), F(3),
+    F(4), F(2), V(1), F(1), F(2), F(2), F(2),
+    F(2), F(1), F(2), F(2), F(4), F(2), F(3),
+    F(6), F(1), C(CAT021_220), F(2), F(2), C(CAT021_110), F(1),
+    F(1), V(1), F(1), R(8), F(7), F(1), C(CAT021_295),
+    S, S, S, S, S, E, E,
+];
+
+/// Byte at `i` or an error about a truncated record
+///
+fn byte(data: &[u8], i: usize) -> Result<u8> {
+    data.get(i)
+        .copied()
+        .ok_or_else(|| anyhow!("truncated record"))
+}
+
+/// Read a FSPEC (or the primary subfield of a compound item), returns the FRNs present,
+/// starting at 1, and the length
+///
+fn fspec(data: &[u8]) -> Result<(Vec<usize>, usize)> {
+    let mut frns = vec![];
+    let mut len = 0;
+    loop {
+        let b = byte(data, len)?;
+        for bit in 0..7 {
+            if b & (0x80 >> bit) != 0 {
+                frns.push(len * 7 + bit + 1);
+            }
+        }
+        len += 1;
+        if b & 1 == 0 {
+            return Ok((frns, len));
+        }
+    }
+}
+
+/// Length of an item starting at `data[0]`
+///
+fn item_len(item: Item, data: &[u8]) -> Result<usize> {
+    let len = match item {
+        Item::Fixed(n) => n,
+        Item::Variable(n) => {
+            let mut len = n;
+            while byte(data, len - 1)? & 1 == 1 {
+                len += n;
+            }
+            len
+        }
+        Item::Repetitive(n) => 1 + byte(data, 0)? as usize * n,
+        Item::Explicit => match byte(data, 0)? {
+            0 => return Err(anyhow!("explicit item of length 0")),
+            n => n as usize,
+        },
+        Item::Compound(subs) => {
+            let (frns, mut len) = fspec(data)?;
+            for frn in frns {
+                let sub = subs.get(frn - 1).copied().unwrap_or(Item::Spare);
+                len += item_len(sub, &data[len.min(data.len())..])?;
+            }
+            len
+        }
+        Item::Spare => return Err(anyhow!("spare bit set")),
+        Item::Rfs => return Err(anyhow!("random field sequencing is not supported")),
+    };
+    if len > data.len() {
+        return Err(anyhow!("truncated record"));
+    }
+    Ok(len)
+}
+
+/// UAP for the record, CAT001 has one for plots and one for tracks depending on I001/020
+///
+fn uap(cat: u8, record: &[u8], frns: &[usize], at: usize) -> Option<&'static [Item]> {
+    match cat {
+        1 => {
+            let at = if frns.first() == Some(&1) { at + 2 } else { at };
+            match frns.contains(&2) && record.get(at).is_some_and(|b| b & 0x80 != 0) {
+                true => Some(CAT001_TRACK),
+                false => Some(CAT001_PLOT),
+            }
+        }
+        2 => Some(CAT002),
+        21 => Some(CAT021),
+        34 => Some(CAT034),
+        48 => Some(CAT048),
+        62 => Some(CAT062),
+        _ => None,
+    }
+}
+
+/// One record: its source if I010 is present and its length if the UAP is known
+///
+fn record(cat: u8, data: &[u8]) -> Result<(Option<DataSourceId>, Option<usize>)> {
+    let (frns, at) = fspec(data)?;
+    let source = match frns.first() {
+        Some(1) => Some(DataSourceId::from([byte(data, at)?, byte(data, at + 1)?])),
+        _ => None,
+    };
+
+    let Some(uap) = uap(cat, data, &frns, at) else {
+        return Ok((source, None));
+    };
+    let mut len = at;
+    for frn in frns {
+        let item = uap
+            .get(frn - 1)
+            .copied()
+            .ok_or_else(|| anyhow!("FRN {} not in the UAP of CAT{:03}", frn, cat))?;
+        len += item_len(item, &data[len.min(data.len())..])?;
+    }
+    Ok((source, Some(len)))
+}
+
+/// Counts for one category and source
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SourceCount {
+    /// ASTERIX category
+    pub category: u8,
+    /// SAC/SIC, none when records have no I010
+    pub source: Option<DataSourceId>,
+    /// Records decoded
+    pub records: usize,
+    /// Data blocks with records from this source
+    pub blocks: usize,
+}
+
+/// Everything found in a recording
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Inspection {
+    /// Size of the recording
+    pub bytes: usize,
+    /// Data blocks read
+    pub blocks: usize,
+    /// Records decoded
+    pub records: usize,
+    /// Per category and source, sorted
+    pub sources: Vec<SourceCount>,
+    /// Categories whose UAP is unknown, only the first record of their blocks was read
+    pub partial: Vec<u8>,
+    /// Problems met, with their offset
+    pub errors: Vec<String>,
+}
+
+/// Walk a raw recording, made of concatenated data blocks
+///
+/// A block which cannot be decoded is skipped, a broken block header ends the walk.  Problems
+/// are not logged, they are all in `Inspection::errors` for the caller.
+///
+pub fn inspect_recording(data: &[u8]) -> Inspection {
+    let mut res = Inspection {
+        bytes: data.len(),
+        ..Default::default()
+    };
+    let mut counts: BTreeMap<(u8, Option<DataSourceId>), (usize, usize)> = BTreeMap::new();
+
+    let mut off = 0;
+    while off < data.len() {
+        if data.len() - off < 3 {
+            res.errors.push(format!(
+                "offset {}: {} trailing bytes",
+                off,
+                data.len() - off
+            ));
+            break;
+        }
+        let cat = data[off];
+        let len = u16::from_be_bytes([data[off + 1], data[off + 2]]) as usize;
+        if len < 3 || off + len > data.len() {
+            res.errors.push(format!(
+                "offset {}: CAT{:03} block of invalid length {}",
+                off, cat, len
+            ));
+            break;
+        }
+        trace!("offset={} cat={} len={}", off, cat, len);
+        res.blocks += 1;
+
+        let block = &data[off + 3..off + len];
+        let mut seen = vec![];
+        let mut at = 0;
+        while at < block.len() {
+            // Some encoders pad blocks with zeroes
+            //
+            if block[at..].iter().all(|b| *b == 0) {
+                break;
+            }
+            match record(cat, &block[at..]) {
+                Ok((source, size)) => {
+                    counts.entry((cat, source)).or_default().0 += 1;
+                    res.records += 1;
+                    if !seen.contains(&source) {
+                        seen.push(source);
+                    }
+                    match size {
+                        Some(size) => at += size,
+                        None => {
+                            if !res.partial.contains(&cat) {
+                                debug!("no UAP for CAT{:03}", cat);
+                                res.partial.push(cat);
+                            }
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    res.errors
+                        .push(format!("offset {}: CAT{:03}: {}", off + 3 + at, cat, e));
+                    break;
+                }
+            }
+        }
+        for source in seen {
+            counts.entry((cat, source)).or_default().1 += 1;
+        }
+        off += len;
+    }
+
+    res.partial.sort();
+    res.sources = counts
+        .into_iter()
+        .map(|((category, source), (records, blocks))| SourceCount {
+            category,
+            source,
+            records,
+            blocks,
+        })
+        .collect();
+    res
+}
+
+/// One line of the report, with the area and label of the SAC
+///
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SourceReport {
+    /// Counts
+    #[serde(flatten)]
+    pub count: SourceCount,
+    /// Area of the SAC, if known
+    pub area: Option<String>,
+    /// Label of the SAC (country, etc.), if known
+    pub label: Option<String>,
+}
+
+/// "1 record", "2 records"
+///
+pub fn count(n: usize, what: &str) -> String {
+    match n {
+        1 => format!("1 {}", what),
+        n => format!("{} {}s", n, what),
+    }
+}
+
+impl Display for SourceReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let c = &self.count;
+        write!(f, "CAT{:03} ", c.category)?;
+        match c.source {
+            Some(id) => write!(f, "{} ({:#})", id, id)?,
+            None => write!(f, "no I010")?,
+        }
+        write!(
+            f,
+            ": {} in {}",
+            count(c.records, "record"),
+            count(c.blocks, "block")
+        )?;
+        match (&self.area, &self.label, c.source) {
+            (Some(area), Some(label), _) => write!(f, ", {} / {}", area, label),
+            (_, _, Some(_)) => write!(f, ", unknown SAC"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Tabular for SourceReport {
+    const HEADER: &'static [&'static str] = &[
+        "Category", "SAC", "SIC", "Decimal", "Region", "Label", "Records", "Blocks",
+    ];
+    const NAME: &'static str = "Sources";
+    const ELEMENT: &'static str = "source";
+
+    fn cells(&self) -> Vec<String> {
+        let (sac, sic, decimal) = match self.count.source {
+            Some(id) => (
+                format!("{:02X}", id.sac),
+                format!("{:02X}", id.sic),
+                id.decimal(),
+            ),
+            None => Default::default(),
+        };
+        vec![
+            format!("{:03}", self.count.category),
+            sac,
+            sic,
+            decimal,
+            self.area.clone().unwrap_or_default(),
+            self.label.clone().unwrap_or_default(),
+            self.count.records.to_string(),
+            self.count.blocks.to_string(),
+        ]
+    }
+}
+
+impl Inspection {
+    /// Label every source with what the SAC list says about it
+    ///
+    pub fn report(&self, areas: &[Area]) -> Vec<SourceReport> {
+        self.sources
+            .iter()
+            .map(|c| {
+                let found = c.source.and_then(|s| lookup_sac(areas, s.sac));
+                SourceReport {
+                    count: c.clone(),
+                    area: found.map(|(a, _)| a.name()),
+                    label: found.map(|(_, l)| l.to_owned()),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    /// Data block around some records
+    ///
+    pub(crate) fn block(cat: u8, records: &[&[u8]]) -> Vec<u8> {
+        let body: Vec<u8> = records.concat();
+        let len = (body.len() + 3) as u16;
+        let mut res = vec![cat];
+        res.extend(len.to_be_bytes());
+        res.extend(body);
+        res
+    }
+
+    /// CAT048 record with I010, I140, I020 (2 parts) and I040
+    ///
+    pub(crate) const CAT048_REC: &[u8] = &[
+        0xF0, // FSPEC: 010 140 020 040
+        0x94, 0x01, // I010
+        0x01, 0x02, 0x03, // I140
+        0x21, 0x00, // I020, two parts
+        0x01, 0x02, 0x03, 0x04, // I040
+    ];
+
+    #[rstest]
+    #[case(&[0x80], vec![1], 1)]
+    #[case(&[0xFF, 0x02], vec![1, 2, 3, 4, 5, 6, 7, 14], 2)]
+    #[case(&[0x01, 0x80], vec![8], 2)]
+    fn test_fspec(#[case] data: &[u8], #[case] frns: Vec<usize>, #[case] len: usize) {
+        assert_eq!((frns, len), fspec(data).unwrap());
+    }
+
+    #[rstest]
+    #[case(F(2), &[1, 2, 3], 2)]
+    #[case(V(1), &[0x01, 0x01, 0x00, 0xFF], 3)]
+    #[case(V(3), &[0, 0, 1, 0, 0, 0], 6)]
+    #[case(R(2), &[2, 1, 2, 3, 4], 5)]
+    #[case(E, &[3, 1, 2], 3)]
+    #[case(C(CAT048_120), &[0xC0, 1, 2, 1, 1, 2, 3, 4, 5, 6], 10)]
+    fn test_item_len(#[case] item: Item, #[case] data: &[u8], #[case] len: usize) {
+        assert_eq!(len, item_len(item, data).unwrap());
+    }
+
+    #[rstest]
+    #[case(F(4), &[1, 2])]
+    #[case(V(1), &[0x01])]
+    #[case(E, &[0])]
+    #[case(S, &[0])]
+    fn test_item_len_bad(#[case] item: Item, #[case] data: &[u8]) {
+        assert!(item_len(item, data).is_err());
+    }
+
+    #[test]
+    fn test_record_cat048() {
+        // 010 140 020 040 130, the last one with SRL and SRR
+        //
+        let rec: &[u8] = &[
+            0xF2, 0x94, 0x01, 1, 2, 3, 0x21, 0x00, 1, 2, 3, 4, 0xC0, 9, 9,
+        ];
+        let (source, len) = record(48, rec).unwrap();
+
+        assert_eq!(Some(DataSourceId::new(0x94, 1)), source);
+        assert_eq!(Some(rec.len()), len);
+    }
+
+    #[test]
+    fn test_record_cat001_track() {
+        // 010 020 (track) 161
+        //
+        let rec: &[u8] = &[0xE0, 0x12, 0x34, 0x80, 0x00, 0x07];
+        assert_eq!(
+            (Some(DataSourceId::new(0x12, 0x34)), Some(6)),
+            record(1, rec).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_inspect_recording_cat034() {
+        // Two sector crossings: 010 000 030 020, the sector number being one octet
+        //
+        let rec1: &[u8] = &[0xF0, 0x12, 0x03, 0x02, 0x01, 0x02, 0x03, 0x10];
+        let rec2: &[u8] = &[0xF0, 0x12, 0x03, 0x02, 0x01, 0x02, 0x04, 0x20];
+        let res = inspect_recording(&block(34, &[rec1, rec2]));
+
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        assert_eq!(2, res.records);
+        assert_eq!(
+            vec![SourceCount {
+                category: 34,
+                source: Some(DataSourceId::new(0x12, 3)),
+                records: 2,
+                blocks: 1,
+            }],
+            res.sources
+        );
+    }
+
+    #[test]
+    fn test_inspect_recording() {
+        let rec2: &[u8] = &[0x80, 0x94, 0x02];
+        let cat062: &[u8] = &[0x90, 0x94, 0x0A, 0x12, 0x34, 0x56];
+        let other: &[u8] = &[0x80, 0x12, 0x01, 0xFF, 0xFF];
+
+        let mut data = block(48, &[CAT048_REC, CAT048_REC, rec2]);
+        data.extend(block(48, &[rec2]));
+        data.extend(block(62, &[cat062]));
+        data.extend(block(240, &[other]));
+
+        let res = inspect_recording(&data);
+
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        assert_eq!(4, res.blocks);
+        assert_eq!(6, res.records);
+        assert_eq!(vec![240], res.partial);
+        assert_eq!(
+            vec![
+                (48, DataSourceId::new(0x94, 1), 2, 1),
+                (48, DataSourceId::new(0x94, 2), 2, 2),
+                (62, DataSourceId::new(0x94, 10), 1, 1),
+                (240, DataSourceId::new(0x12, 1), 1, 1),
+            ],
+            res.sources
+                .iter()
+                .map(|s| (s.category, s.source.unwrap(), s.records, s.blocks))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_inspect_recording_broken() {
+        let mut data = block(48, &[CAT048_REC]);
+        data.extend([48, 0x00, 0x40, 0x80]);
+
+        let res = inspect_recording(&data);
+
+        assert_eq!(1, res.blocks);
+        assert_eq!(1, res.records);
+        assert_eq!(1, res.errors.len());
+        assert!(res.errors[0].contains("invalid length 64"));
+    }
+
+    #[test]
+    fn test_inspection_report() {
+        let mut a = Area::new("Asia");
+        a.add("94", "Vietnam");
+        let res = inspect_recording(&block(48, &[CAT048_REC]));
+        let report = res.report(&[a]);
+
+        assert_eq!(Some("Asia".to_owned()), report[0].area);
+        assert_eq!(Some("Vietnam".to_owned()), report[0].label);
+    }
+
+    #[test]
+    fn test_source_report_display_cells() {
+        let known = SourceReport {
+            count: SourceCount {
+                category: 48,
+                source: Some(DataSourceId::new(0x94, 1)),
+                records: 12,
+                blocks: 3,
+            },
+            area: Some("Asia".to_owned()),
+            label: Some("Vietnam".to_owned()),
+        };
+        let none = SourceReport {
+            count: SourceCount {
+                category: 62,
+                source: None,
+                records: 2,
+                blocks: 1,
+            },
+            area: None,
+            label: None,
+        };
+
+        assert_eq!(
+            "CAT048 94/01 (148/1): 12 records in 3 blocks, Asia / Vietnam",
+            known.to_string()
+        );
+        assert_eq!("CAT062 no I010: 2 records in 1 block", none.to_string());
+        assert_eq!(
+            vec!["048", "94", "01", "148/1", "Asia", "Vietnam", "12", "3"],
+            known.cells()
+        );
+        assert_eq!(vec!["062", "", "", "", "", "", "2", "1"], none.cells());
+    }
+}
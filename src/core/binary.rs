@@ -0,0 +1,90 @@
+//! Module dealing with compact binary formats: MessagePack, CBOR and bincode
+//!
+//! Every format comes with its reading counterpart so the list of areas can be shipped
+//! around (config bundles, etc.) and loaded back without the network.  Writers take anything
+//! serializable, e.g. the list of categories.
+//!
+
+use anyhow::Result;
+use log::trace;
+use serde::Serialize;
+
+use crate::core::Area;
+
+/// Serialize into MessagePack, structs are maps with field names for interoperability
+///
+pub fn to_msgpack<T: Serialize + ?Sized>(data: &T) -> Result<Vec<u8>> {
+    trace!("Generating msgpack output…");
+    Ok(rmp_serde::to_vec_named(data)?)
+}
+
+/// Read back MessagePack from [`to_msgpack`]
+///
+pub fn from_msgpack(data: &[u8]) -> Result<Vec<Area>> {
+    Ok(rmp_serde::from_slice(data)?)
+}
+
+/// Serialize into CBOR
+///
+pub fn to_cbor<T: Serialize + ?Sized>(data: &T) -> Result<Vec<u8>> {
+    trace!("Generating cbor output…");
+    let mut out = vec![];
+    ciborium::into_writer(data, &mut out)?;
+    Ok(out)
+}
+
+/// Read back CBOR from [`to_cbor`]
+///
+pub fn from_cbor(data: &[u8]) -> Result<Vec<Area>> {
+    Ok(ciborium::from_reader(data)?)
+}
+
+/// Serialize with bincode, the fastest to load but only readable from Rust
+///
+pub fn to_bincode<T: Serialize + ?Sized>(data: &T) -> Result<Vec<u8>> {
+    trace!("Generating bincode output…");
+    Ok(bincode::serialize(data)?)
+}
+
+/// Read back bincode from [`to_bincode`]
+///
+pub fn from_bincode(data: &[u8]) -> Result<Vec<Area>> {
+    Ok(bincode::deserialize(data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<Area> {
+        let mut a = Area::new("Asia");
+        a.add("94", "Vietnam");
+        a.add("A0...C3", "Reserved");
+        vec![a, Area::new("Empty")]
+    }
+
+    #[test]
+    fn test_msgpack_roundtrip() {
+        let data = to_msgpack(&sample()).unwrap();
+        assert_eq!(sample(), from_msgpack(&data).unwrap());
+    }
+
+    #[test]
+    fn test_cbor_roundtrip() {
+        let data = to_cbor(&sample()).unwrap();
+        assert_eq!(sample(), from_cbor(&data).unwrap());
+    }
+
+    #[test]
+    fn test_bincode_roundtrip() {
+        let data = to_bincode(&sample()).unwrap();
+        assert_eq!(sample(), from_bincode(&data).unwrap());
+    }
+
+    #[test]
+    fn test_garbage() {
+        assert!(from_msgpack(b"garbage").is_err());
+        assert!(from_cbor(b"garbage").is_err());
+        assert!(from_bincode(b"garbage").is_err());
+    }
+}
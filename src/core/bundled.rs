@@ -0,0 +1,62 @@
+//! Module giving access to the snapshot of the SAC list embedded in the crate
+//!
+//! `data/sac.json` is a JSON envelope generated by `fetch-sac` itself and included at build
+//! time, so the library works without any network access.  Refresh it with:
+//!
+//! ```text
+//! cargo run --features live -- -F json-envelope -o data/sac.json
+//! ```
+//!
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+use crate::core::Envelope;
+
+/// The snapshot itself
+///
+const BUNDLED: &str = include_str!("../../data/sac.json");
+
+/// Return the bundled snapshot, see `Envelope::retrieved_at` for its date
+///
+/// Fails if `data/sac.json` is invalid or has no area, an empty list is never what the caller
+/// wants.
+///
+pub fn bundled() -> Result<Envelope> {
+    let env: Envelope = serde_json::from_str(BUNDLED)
+        .map_err(|e| anyhow!("bundled data/sac.json is invalid: {}", e))?;
+    if env.areas.is_empty() {
+        return Err(anyhow!(
+            "bundled data/sac.json is empty, refresh it with the live feature or use -i"
+        ));
+    }
+    Ok(env)
+}
+
+/// Date of the bundled snapshot
+///
+pub fn bundled_date() -> Result<DateTime<Utc>> {
+    Ok(bundled()?.retrieved_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{content_hash, PAGE, SCHEMA_VERSION};
+
+    #[test]
+    fn test_bundled() {
+        let env: Envelope = serde_json::from_str(BUNDLED).unwrap();
+
+        assert_eq!(SCHEMA_VERSION, env.schema_version);
+        assert_eq!(PAGE, env.source_url);
+        assert_eq!(env.hash, content_hash(&env.areas).unwrap());
+
+        // The snapshot is only usable once refreshed from the page
+        //
+        match env.areas.is_empty() {
+            true => assert!(bundled().is_err()),
+            false => assert_eq!(env.retrieved_at, bundled_date().unwrap()),
+        }
+    }
+}
@@ -0,0 +1,559 @@
+//! Module dealing with the second dataset of the page: ASTERIX categories and the current
+//! edition of their specification
+//!
+//! Categories are found either in tables with a "Category" column or in links like
+//! "Cat 062 – SDPS Track Messages", the edition and date being looked for in the same row or
+//! list item.  Everything found about a category is merged.
+//!
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, info, trace};
+use regex::Regex;
+use schemars::JsonSchema;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::core::{
+    categories_from_protobuf, cell_text, is_header_row, parse_updated, row_cells, ScrapeOptions,
+    Tabular, PAGE, SCHEMA_VERSION,
+};
+
+/// One document about a category
+///
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Link {
+    /// Text of the link
+    pub title: String,
+    /// Absolute URL
+    pub url: String,
+}
+
+/// One ASTERIX category
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Category {
+    /// Category number, e.g. 62
+    pub number: u8,
+    /// Title, e.g. "SDPS Track Messages"
+    pub title: String,
+    /// Current edition of the specification, e.g. "1.19"
+    pub edition: Option<String>,
+    /// Date of that edition, as published
+    pub date: Option<String>,
+    /// Documents (specification, XML definition, etc.)
+    pub links: Vec<Link>,
+}
+
+impl Category {
+    /// Usual name, e.g. "CAT062"
+    ///
+    pub fn name(&self) -> String {
+        format!("CAT{:03}", self.number)
+    }
+
+    /// Add what we did not know yet from `other`
+    ///
+    fn merge(&mut self, other: Category) {
+        if self.title.is_empty() {
+            self.title = other.title;
+        }
+        if self.edition.is_none() {
+            self.edition = other.edition;
+        }
+        if self.date.is_none() {
+            self.date = other.date;
+        }
+        for link in other.links {
+            if !self.links.iter().any(|l| l.url == link.url) {
+                self.links.push(link);
+            }
+        }
+    }
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {}", self.name(), self.title)?;
+        if let Some(edition) = &self.edition {
+            write!(f, " (edition {}", edition)?;
+            if let Some(date) = &self.date {
+                write!(f, ", {}", date)?;
+            }
+            write!(f, ")")?;
+        }
+        self.links
+            .iter()
+            .try_for_each(|l| write!(f, "\n  {} <{}>", l.title, l.url))
+    }
+}
+
+impl Tabular for Category {
+    const HEADER: &'static [&'static str] =
+        &["Category", "Name", "Title", "Edition", "Date", "Links"];
+    const NAME: &'static str = "Categories";
+    const ELEMENT: &'static str = "category";
+
+    /// Links are separated by spaces
+    ///
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.number.to_string(),
+            self.name(),
+            self.title.to_owned(),
+            self.edition.clone().unwrap_or_default(),
+            self.date.clone().unwrap_or_default(),
+            self.links
+                .iter()
+                .map(|l| l.url.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        ]
+    }
+}
+
+/// All categories with where and when they were retrieved, like `Envelope` for areas
+///
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Categories {
+    /// Version of this format
+    pub schema_version: u32,
+    /// Where the data comes from
+    pub source_url: String,
+    /// When the data was retrieved
+    pub retrieved_at: DateTime<Utc>,
+    /// Last update of the page itself, as published
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_updated: Option<String>,
+    /// `sha256:` followed by the hash of the compact JSON of `categories`
+    pub hash: String,
+    /// The data itself, sorted by number
+    pub categories: Vec<Category>,
+}
+
+impl Categories {
+    /// Wrap `categories` retrieved from the official page at `retrieved`
+    ///
+    pub fn new(categories: &[Category], retrieved: DateTime<Utc>) -> Result<Self> {
+        let data = serde_json::to_vec(categories)?;
+        Ok(Categories {
+            schema_version: SCHEMA_VERSION,
+            source_url: PAGE.to_owned(),
+            retrieved_at: retrieved,
+            page_updated: None,
+            hash: format!("sha256:{:x}", Sha256::digest(data)),
+            categories: categories.to_vec(),
+        })
+    }
+}
+
+/// Make a link absolute by resolving it against the page, `None` for links that are not
+/// http(s) like `mailto:`
+///
+fn absolute(href: &str) -> Option<String> {
+    let url = Url::parse(PAGE).ok()?.join(href).ok()?;
+    match url.scheme() {
+        "http" | "https" => Some(url.into()),
+        _ => None,
+    }
+}
+
+/// All the links inside an element
+///
+fn links(e: ElementRef, opts: &ScrapeOptions) -> Vec<Link> {
+    let sel = Selector::parse("a[href]").unwrap();
+    e.select(&sel)
+        .filter_map(|a| {
+            Some(Link {
+                title: opts.clean(&cell_text(a)),
+                url: absolute(a.value().attr("href")?)?,
+            })
+        })
+        .collect()
+}
+
+/// Regexes for the category itself, its edition and date
+///
+struct Patterns {
+    cat: Regex,
+    number: Regex,
+    edition: Regex,
+    date: Regex,
+}
+
+impl Patterns {
+    fn new() -> Self {
+        Patterns {
+            cat: Regex::new(r"(?i)\bcat(?:egory)?\.?\s*0*(\d{1,3})\b\s*[-–—:]?\s*(.*)").unwrap(),
+            number: Regex::new(r"(?i)^\s*(?:cat(?:egory)?\.?\s*)?0*(\d+)").unwrap(),
+            edition: Regex::new(r"(?i)\b(?:edition|ed\.|version)\s*:?\s*(\d+(?:\.\d+)*)").unwrap(),
+            date: Regex::new(
+                r"(?i)\b(\d{4}-\d{2}-\d{2}|\d{1,2}/\d{1,2}/\d{4}|(?:\d{1,2}\s+)?(?:jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)[[:alpha:]]*\.?\s+\d{4})\b",
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Number from "62", "062" or "CAT062", only the leading one so that "CAT001 Part 2" is 1
+    ///
+    fn number(&self, s: &str) -> Option<u8> {
+        self.number.captures(s).and_then(|c| c[1].parse().ok())
+    }
+
+    /// Edition from "1.19" or "Edition 1.19"
+    ///
+    fn edition(&self, s: &str) -> Option<String> {
+        match self.edition.captures(s) {
+            Some(c) => Some(c[1].to_owned()),
+            None if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit() || c == '.') => {
+                Some(s.to_owned())
+            }
+            None => None,
+        }
+    }
+
+    fn date(&self, s: &str) -> Option<String> {
+        self.date.captures(s).map(|c| c[1].to_owned())
+    }
+
+    /// Title without the edition and date
+    ///
+    fn title(&self, s: &str) -> String {
+        let s = self.edition.replace_all(s, "");
+        let s = self.date.replace_all(&s, "");
+        s.trim_matches(|c: char| c.is_whitespace() || "-–—,:()".contains(c))
+            .to_owned()
+    }
+}
+
+/// Categories from tables with a "Category" column
+///
+fn from_tables(doc: &Html, pat: &Patterns, opts: &ScrapeOptions) -> Vec<Category> {
+    let table = Selector::parse("table").unwrap();
+    let tr = Selector::parse("tr").unwrap();
+    // A key has to start a word of the header, "Cat." or "Category" but not "Location"
+    //
+    let find = |cols: &[String], keys: &[&str]| {
+        cols.iter().position(|c| {
+            let c = c.to_lowercase();
+            c.split(|ch: char| !ch.is_alphanumeric())
+                .any(|w| keys.iter().any(|k| w.starts_with(k)))
+        })
+    };
+
+    let mut res = vec![];
+    for t in doc.select(&table) {
+        let mut rows = t.select(&tr);
+        let cols: Vec<String> = match rows.next() {
+            Some(hdr) if is_header_row(hdr) => {
+                row_cells(hdr).iter().map(|c| opts.clean(c)).collect()
+            }
+            _ => continue,
+        };
+        let Some(num) = find(&cols, &["cat"]) else {
+            continue;
+        };
+        debug!("category table with {:?}", cols);
+
+        let title =
+            find(&cols, &["title", "name", "description"]).unwrap_or(if num == 0 { 1 } else { 0 });
+        let edition = find(&cols, &["edition", "version"]);
+        let date = find(&cols, &["date"]);
+
+        for row in rows {
+            let cells: Vec<String> = row_cells(row).iter().map(|c| opts.clean(c)).collect();
+            let Some(number) = cells.get(num).and_then(|c| pat.number(c)) else {
+                continue;
+            };
+            let get = |i: Option<usize>| i.and_then(|i| cells.get(i)).cloned();
+            res.push(Category {
+                number,
+                title: get(Some(title)).unwrap_or_default(),
+                edition: get(edition).and_then(|e| pat.edition(&e)),
+                date: get(date).and_then(|d| pat.date(&d).or(Some(d))),
+                links: links(row, opts),
+            });
+        }
+    }
+    res
+}
+
+/// Categories from links like "Cat 062 – SDPS Track Messages", the edition and date are looked
+/// for in the text around the link
+///
+fn from_links(doc: &Html, pat: &Patterns, opts: &ScrapeOptions) -> Vec<Category> {
+    let sel = Selector::parse("a[href]").unwrap();
+
+    doc.select(&sel)
+        .filter_map(|a| {
+            let text = opts.clean(&cell_text(a));
+            let c = pat.cat.captures(&text)?;
+            let url = absolute(a.value().attr("href")?)?;
+            let number = c[1].parse().ok()?;
+
+            let around = a
+                .parent()
+                .and_then(ElementRef::wrap)
+                .map(|p| opts.clean(&cell_text(p)))
+                .unwrap_or_default();
+            trace!("link {} in {}", text, around);
+
+            Some(Category {
+                number,
+                title: pat.title(&c[2]),
+                edition: pat.edition(&around),
+                date: pat.date(&around),
+                links: vec![Link {
+                    title: text.clone(),
+                    url,
+                }],
+            })
+        })
+        .collect()
+}
+
+/// Extract the list of categories from the page, sorted by number
+///
+pub fn scrape_categories(doc: String, opts: &ScrapeOptions) -> Result<Vec<Category>> {
+    let doc = Html::parse_document(&doc);
+    let pat = Patterns::new();
+
+    let mut all: BTreeMap<u8, Category> = BTreeMap::new();
+    for cat in from_tables(&doc, &pat, opts)
+        .into_iter()
+        .chain(from_links(&doc, &pat, opts))
+    {
+        match all.get_mut(&cat.number) {
+            Some(c) => c.merge(cat),
+            None => {
+                all.insert(cat.number, cat);
+            }
+        }
+    }
+    Ok(all.into_values().collect())
+}
+
+/// Scrape the categories and wrap them with the page metadata
+///
+pub fn scrape_categories_page(
+    doc: String,
+    retrieved: DateTime<Utc>,
+    opts: &ScrapeOptions,
+) -> Result<Categories> {
+    let updated = parse_updated(&Html::parse_document(&doc));
+    let mut res = Categories::new(&scrape_categories(doc, opts)?, retrieved)?;
+    res.page_updated = updated;
+    Ok(res)
+}
+
+/// Fetch the official page and scrape the categories
+///
+#[cfg(feature = "live")]
+pub fn fetch_categories(opts: &ScrapeOptions) -> Result<Categories> {
+    let doc = reqwest::blocking::get(PAGE)?.text()?;
+    let res = scrape_categories_page(doc, Utc::now(), opts)?;
+    info!("{} categories found", res.categories.len());
+    Ok(res)
+}
+
+/// Load categories from a saved copy of the page or from JSON or protobuf generated earlier,
+/// either the envelope or the compact list for JSON
+///
+/// For the page and the list, the modification time of the file is used as retrieval date.
+///
+pub fn load_categories(path: &Path, opts: &ScrapeOptions) -> Result<Categories> {
+    info!("Reading {}...", path.to_string_lossy());
+    let data = fs::read(path)?;
+    let mtime: DateTime<Utc> = fs::metadata(path)?.modified()?.into();
+
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    debug!("ext={}", ext);
+    if ext == "pb" {
+        return categories_from_protobuf(&data);
+    }
+    let data = String::from_utf8(data)?;
+    match ext {
+        "html" | "htm" => scrape_categories_page(data, mtime, opts),
+        "json" => match serde_json::from_str::<Categories>(&data) {
+            Ok(res) => Ok(res),
+            Err(_) => Categories::new(&serde_json::from_str::<Vec<Category>>(&data)?, mtime),
+        },
+        _ => Err(anyhow!(
+            "unknown input format for categories in {}",
+            path.to_string_lossy()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::core::categories_to_protobuf;
+
+    const PAGE_SAMPLE: &str = include_str!("../../testdata/asterix.html");
+
+    #[rstest]
+    #[case(
+        "/sites/default/files/cat062.pdf",
+        Some("https://www.eurocontrol.int/sites/default/files/cat062.pdf")
+    )]
+    #[case("https://example.com/x.pdf", Some("https://example.com/x.pdf"))]
+    #[case("//example.com/x.pdf", Some("https://example.com/x.pdf"))]
+    #[case("mailto:asterix@eurocontrol.int", None)]
+    #[case("javascript:void(0)", None)]
+    fn test_absolute(#[case] href: &str, #[case] res: Option<&str>) {
+        assert_eq!(res.map(str::to_owned), absolute(href));
+    }
+
+    #[rstest]
+    #[case("62", Some(62))]
+    #[case("062", Some(62))]
+    #[case("CAT062", Some(62))]
+    #[case("Cat. 0", Some(0))]
+    #[case("CAT001 Part 2", Some(1))]
+    #[case("Category 21 (2.6)", Some(21))]
+    #[case("CAT999", None)]
+    #[case("Part 2", None)]
+    fn test_number(#[case] s: &str, #[case] res: Option<u8>) {
+        assert_eq!(res, Patterns::new().number(s));
+    }
+
+    #[rstest]
+    #[case("Edition 1.19", Some("1.19"))]
+    #[case("1.4", Some("1.4"))]
+    #[case("Ed. 2.0 - May 2020", Some("2.0"))]
+    #[case("latest", None)]
+    fn test_edition(#[case] s: &str, #[case] res: Option<&str>) {
+        assert_eq!(res.map(|s| s.to_owned()), Patterns::new().edition(s));
+    }
+
+    #[rstest]
+    #[case("Edition 1.19 - 12 March 2023", Some("12 March 2023"))]
+    #[case("Edition 1.4, August 2016", Some("August 2016"))]
+    #[case("2021-01-31", Some("2021-01-31"))]
+    #[case("Edition 1.4", None)]
+    fn test_date(#[case] s: &str, #[case] res: Option<&str>) {
+        assert_eq!(res.map(|s| s.to_owned()), Patterns::new().date(s));
+    }
+
+    #[test]
+    fn test_scrape_categories() {
+        let cats = scrape_categories(PAGE_SAMPLE.to_owned(), &ScrapeOptions::default()).unwrap();
+
+        assert_eq!(
+            vec![1, 21, 62],
+            cats.iter().map(|c| c.number).collect::<Vec<_>>()
+        );
+
+        // From the table
+        //
+        assert_eq!("CAT001", cats[0].name());
+        assert_eq!("Monoradar Target Reports", cats[0].title);
+        assert_eq!(Some("1.4".to_owned()), cats[0].edition);
+        assert_eq!(Some("August 2016".to_owned()), cats[0].date);
+        assert_eq!(
+            "https://www.eurocontrol.int/sites/default/files/cat001p2_ed1.4.pdf",
+            cats[0].links[0].url
+        );
+
+        // Table and link merged
+        //
+        assert_eq!("ADS-B Target Reports", cats[1].title);
+        assert_eq!(2, cats[1].links.len());
+
+        // Only from a link
+        //
+        assert_eq!("SDPS Track Messages", cats[2].title);
+        assert_eq!(Some("1.19".to_owned()), cats[2].edition);
+        assert_eq!(Some("12 March 2023".to_owned()), cats[2].date);
+    }
+
+    #[rstest]
+    #[case("<tr><th>Location</th><th>Name</th></tr><tr><td>62</td><td>Paris</td></tr>", vec![])]
+    #[case("<tr><th>Application</th><th>Title</th></tr><tr><td>1</td><td>Radar</td></tr>", vec![])]
+    #[case("<tr><th>Cat.</th><th>Title</th></tr><tr><td>62</td><td>SDPS</td></tr>", vec![62])]
+    #[case("<tr><th>ASTERIX Category</th><th>Title</th></tr><tr><td>1</td><td>Radar</td></tr>", vec![1])]
+    fn test_from_tables_header(#[case] rows: &str, #[case] res: Vec<u8>) {
+        let doc = Html::parse_document(&format!("<table>{}</table>", rows));
+        let cats = from_tables(&doc, &Patterns::new(), &ScrapeOptions::default());
+
+        assert_eq!(res, cats.iter().map(|c| c.number).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_category_display_cells() {
+        let cat = Category {
+            number: 1,
+            title: "Monoradar Target Reports".to_owned(),
+            edition: Some("1.4".to_owned()),
+            date: Some("August 2016".to_owned()),
+            links: vec![Link {
+                title: "Specification".to_owned(),
+                url: "https://www.eurocontrol.int/cat001.pdf".to_owned(),
+            }],
+        };
+
+        assert_eq!(
+            "CAT001 = Monoradar Target Reports (edition 1.4, August 2016)\n  \
+            Specification <https://www.eurocontrol.int/cat001.pdf>",
+            cat.to_string()
+        );
+        assert_eq!(
+            vec![
+                "1",
+                "CAT001",
+                "Monoradar Target Reports",
+                "1.4",
+                "August 2016",
+                "https://www.eurocontrol.int/cat001.pdf"
+            ],
+            cat.cells()
+        );
+    }
+
+    #[test]
+    fn test_categories_new() {
+        let cats = scrape_categories(PAGE_SAMPLE.to_owned(), &ScrapeOptions::default()).unwrap();
+        let all = Categories::new(&cats, Utc::now()).unwrap();
+
+        assert_eq!(3, all.categories.len());
+        assert!(all.hash.starts_with("sha256:"));
+    }
+
+    #[test]
+    fn test_load_categories() {
+        let opts = ScrapeOptions::default();
+        let res = load_categories(Path::new("testdata/asterix.html"), &opts).unwrap();
+
+        assert_eq!(3, res.categories.len());
+        assert_eq!(Some("12 March 2023".to_owned()), res.page_updated);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cats.json");
+        fs::write(&path, serde_json::to_string(&res).unwrap()).unwrap();
+        let back = load_categories(&path, &opts).unwrap();
+        assert_eq!(res.hash, back.hash);
+        assert_eq!(res.page_updated, back.page_updated);
+
+        fs::write(&path, serde_json::to_string(&res.categories).unwrap()).unwrap();
+        let back = load_categories(&path, &opts).unwrap();
+        assert_eq!(res.categories, back.categories);
+
+        let path = dir.path().join("cats.pb");
+        fs::write(&path, categories_to_protobuf(&res).unwrap()).unwrap();
+        let back = load_categories(&path, &opts).unwrap();
+        assert_eq!(res.categories, back.categories);
+        assert_eq!(res.page_updated, back.page_updated);
+
+        assert!(load_categories(Path::new("Cargo.toml"), &opts).is_err());
+    }
+}
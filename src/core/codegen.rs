@@ -0,0 +1,609 @@
+//! Module generating source code with a static SAC table for other programs
+//!
+//! We can generate a Rust module, a C header and a Python module.
+//!
+//! The generated Rust module has no dependency and can be written from a `build.rs`:
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//!
+//! let out = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("sac.rs");
+//! let env = fetch_sac::load_file("sac.json".as_ref()).unwrap();
+//! std::fs::write(out, fetch_sac::to_rust(&env.areas, env.retrieved_at).unwrap()).unwrap();
+//! ```
+//!
+//! and then used with `include!(concat!(env!("OUT_DIR"), "/sac.rs"));`.
+//!
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::trace;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::core::{Area, PAGE, SAC};
+
+/// Single codes as `(sac, area, label)` sorted by code and ranges as `(lo, hi, area, label)`
+///
+type Tables = (Vec<(u8, String, String)>, Vec<(u8, u8, String, String)>);
+
+/// Split all codes between single ones and ranges, anything not parsing is ignored
+///
+pub(crate) fn tables(areas: &[Area]) -> Tables {
+    let mut sacs = vec![];
+    let mut ranges = vec![];
+
+    for area in areas {
+        for (code, label) in area {
+            match SAC::try_from(code.as_str()).unwrap_or_default() {
+                SAC::Hex(s) => {
+                    if let Ok(v) = u8::from_str_radix(&s, 16) {
+                        sacs.push((v, area.name(), label.to_owned()));
+                    }
+                }
+                SAC::Range { lo, hi } => {
+                    if let (Ok(lo), Ok(hi)) = (u8::try_from(lo), u8::try_from(hi)) {
+                        ranges.push((lo, hi, area.name(), label.to_owned()));
+                    }
+                }
+                SAC::Empty => (),
+            }
+        }
+    }
+    sacs.sort();
+    ranges.sort();
+    (sacs, ranges)
+}
+
+/// Generate a Rust module with `SACS` and `RANGES` tables and a `lookup()` function
+///
+pub fn to_rust(areas: &[Area], retrieved: DateTime<Utc>) -> Result<String> {
+    trace!("Generating rust code…");
+    let (sacs, ranges) = tables(areas);
+    let date = retrieved.format("%Y-%m-%d %H:%M:%S UTC");
+
+    let sacs = sacs
+        .iter()
+        .map(|(v, area, label)| format!("    (0x{:02X}, {:?}, {:?}),", v, area, label))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let ranges = ranges
+        .iter()
+        .map(|(lo, hi, area, label)| {
+            format!("    (0x{:02X}, 0x{:02X}, {:?}, {:?}),", lo, hi, area, label)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(format!(
+        r##"// SAC codes from {PAGE}
+//
+// Retrieved on {date}.
+// Generated by fetch-sac {}, do not edit.
+
+/// Date the SAC codes were retrieved on, RFC 3339
+pub const RETRIEVED_AT: &str = "{}";
+
+/// Single codes as `(sac, area, label)`, sorted by code, retrieved on {date}
+pub const SACS: &[(u8, &str, &str)] = &[
+{sacs}
+];
+
+/// Ranges of codes as `(lo, hi, area, label)`, retrieved on {date}
+pub const RANGES: &[(u8, u8, &str, &str)] = &[
+{ranges}
+];
+
+/// Find the area and label of a SAC, looking at single codes first then ranges
+pub fn lookup(sac: u8) -> Option<(&'static str, &'static str)> {{
+    match SACS.binary_search_by_key(&sac, |e| e.0) {{
+        Ok(i) => Some((SACS[i].1, SACS[i].2)),
+        Err(_) => RANGES
+            .iter()
+            .find(|r| r.0 <= sac && sac <= r.1)
+            .map(|r| (r.2, r.3)),
+    }}
+}}
+"##,
+        env!("CARGO_PKG_VERSION"),
+        retrieved.to_rfc3339(),
+    ))
+}
+
+/// Escape a string for a C literal, non-ASCII bytes are written as octal escapes
+///
+pub(crate) fn c_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for b in s.bytes() {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'?' => out.push_str("\\?"),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Name of the enum member for a single code, e.g. `SAC_ASIA_PACIFIC_94`
+///
+/// Accents are removed and anything else outside of `[A-Za-z0-9]` becomes `_` so that this is
+/// always a valid C identifier.
+///
+fn c_enum_name(area: &str, sac: u8) -> String {
+    let area = area
+        .nfd()
+        .filter(|c| !('\u{300}'..='\u{36f}').contains(c))
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect::<String>();
+    let area = area
+        .split('_')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    match area.is_empty() {
+        true => format!("SAC_{:02X}", sac),
+        false => format!("SAC_{}_{:02X}", area, sac),
+    }
+}
+
+/// Enum members for all single codes, duplicate names get `_2`, `_3`…
+///
+fn c_enum_names(sacs: &[(u8, String, String)]) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    sacs.iter()
+        .map(|(v, area, _)| {
+            let name = c_enum_name(area, *v);
+            let n = seen.entry(name.clone()).or_default();
+            *n += 1;
+            match *n {
+                1 => name,
+                n => format!("{}_{}", name, n),
+            }
+        })
+        .collect()
+}
+
+/// Generate a header-only C file with an enum of all single codes, a `sac_table` of all codes and
+/// ranges and the `sac_name()`/`sac_area()` lookup functions
+///
+/// The table always ends with a `{ 0, 0, NULL, NULL }` entry, not counted in `SAC_TABLE_LEN`,
+/// so that it is valid C even without any code, and the enum is only there if there are
+/// single codes.
+///
+pub fn to_c(areas: &[Area], retrieved: DateTime<Utc>) -> Result<String> {
+    trace!("Generating C code…");
+    let (sacs, ranges) = tables(areas);
+    let date = retrieved.format("%Y-%m-%d %H:%M:%S UTC");
+
+    let members = c_enum_names(&sacs)
+        .iter()
+        .zip(&sacs)
+        .map(|(name, (v, _, _))| format!("    {} = 0x{:02X},", name, v))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let codes = match members.is_empty() {
+        true => "/* No single codes */".to_owned(),
+        false => {
+            format!("/* Single codes, retrieved on {date} */\nenum sac_code {{\n{members}\n}};")
+        }
+    };
+    let len = sacs.len() + ranges.len();
+    let entries = sacs
+        .iter()
+        .map(|(v, area, label)| (*v, *v, area, label))
+        .chain(
+            ranges
+                .iter()
+                .map(|(lo, hi, area, label)| (*lo, *hi, area, label)),
+        )
+        .map(|(lo, hi, area, label)| {
+            format!(
+                "    {{ 0x{:02X}, 0x{:02X}, {}, {} }},",
+                lo,
+                hi,
+                c_string(area),
+                c_string(label)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(format!(
+        r##"/* SAC codes from {PAGE}
+ *
+ * Retrieved on {date}.
+ * Generated by fetch-sac {}, do not edit.
+ */
+
+#ifndef FETCH_SAC_H
+#define FETCH_SAC_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+/* Date the SAC codes were retrieved on, RFC 3339 */
+#define SAC_RETRIEVED_AT "{}"
+
+{codes}
+
+/* One code (lo == hi) or range of codes */
+struct sac_entry {{
+    uint8_t lo;
+    uint8_t hi;
+    const char *area;
+    const char *label;
+}};
+
+/* Number of entries in sac_table, without the final empty one */
+#define SAC_TABLE_LEN {len}
+
+/* Single codes sorted by code first, then ranges */
+static const struct sac_entry sac_table[] = {{
+{entries}
+    {{ 0, 0, NULL, NULL }}
+}};
+
+/* Find the entry for a SAC, single codes are looked at before ranges, NULL if not found */
+static inline const struct sac_entry *sac_lookup(uint8_t sac)
+{{
+    const struct sac_entry *e;
+
+    for (e = sac_table; e->area != NULL; e++) {{
+        if (e->lo <= sac && sac <= e->hi) {{
+            return e;
+        }}
+    }}
+    return NULL;
+}}
+
+/* Label of a SAC, NULL if not found */
+static inline const char *sac_name(uint8_t sac)
+{{
+    const struct sac_entry *e = sac_lookup(sac);
+
+    return e ? e->label : NULL;
+}}
+
+/* Area of a SAC, NULL if not found */
+static inline const char *sac_area(uint8_t sac)
+{{
+    const struct sac_entry *e = sac_lookup(sac);
+
+    return e ? e->area : NULL;
+}}
+
+#endif /* FETCH_SAC_H */
+"##,
+        env!("CARGO_PKG_VERSION"),
+        retrieved.to_rfc3339(),
+    ))
+}
+
+/// Escape a string for a Python literal, non-ASCII characters are kept as-is (UTF-8 source)
+///
+fn py_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Generate a Python module with `SACS` and `RANGES` and the `lookup()`/`sac_name()` helpers
+///
+pub fn to_python(areas: &[Area], retrieved: DateTime<Utc>) -> Result<String> {
+    trace!("Generating python code…");
+    let (sacs, ranges) = tables(areas);
+    let date = retrieved.format("%Y-%m-%d %H:%M:%S UTC");
+
+    let sacs = sacs
+        .iter()
+        .map(|(v, area, label)| {
+            format!(
+                "    0x{:02X}: ({}, {}),",
+                v,
+                py_string(area),
+                py_string(label)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let ranges = ranges
+        .iter()
+        .map(|(lo, hi, area, label)| {
+            format!(
+                "    (0x{:02X}, 0x{:02X}, {}, {}),",
+                lo,
+                hi,
+                py_string(area),
+                py_string(label)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(format!(
+        r##""""SAC codes from {PAGE}
+
+Retrieved on {date}.
+Generated by fetch-sac {}, do not edit.
+"""
+
+from typing import Dict, List, Optional, Tuple
+
+#: Date the SAC codes were retrieved on, RFC 3339
+RETRIEVED_AT = "{}"
+
+#: Single codes as ``sac: (area, label)``, retrieved on {date}
+SACS: Dict[int, Tuple[str, str]] = {{
+{sacs}
+}}
+
+#: Ranges of codes as ``(lo, hi, area, label)``, retrieved on {date}
+RANGES: List[Tuple[int, int, str, str]] = [
+{ranges}
+]
+
+
+def lookup(sac: int) -> Optional[Tuple[str, str]]:
+    """Find the area and label of a SAC, looking at single codes first then ranges"""
+    if sac in SACS:
+        return SACS[sac]
+    for lo, hi, area, label in RANGES:
+        if lo <= sac <= hi:
+            return (area, label)
+    return None
+
+
+def sac_name(sac: int) -> Optional[str]:
+    """Label of a SAC, None if not found"""
+    found = lookup(sac)
+    return found[1] if found else None
+
+
+def sac_area(sac: int) -> Optional[str]:
+    """Area of a SAC, None if not found"""
+    found = lookup(sac)
+    return found[0] if found else None
+"##,
+        env!("CARGO_PKG_VERSION"),
+        retrieved.to_rfc3339(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process::Command;
+
+    use chrono::TimeZone;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn sample() -> Vec<Area> {
+        let mut a = Area::new("Asia");
+        a.add("94", "Vietnam \"south\"");
+        a.add("A0...C3", "Reserved");
+        let mut b = Area::new("Europe");
+        b.add("10", "Somewhere");
+        vec![a, b]
+    }
+
+    #[test]
+    fn test_tables() {
+        let areas = sample();
+        let (sacs, ranges) = tables(&areas);
+
+        assert_eq!(2, sacs.len());
+        assert_eq!((0x10, "Europe".to_owned(), "Somewhere".to_owned()), sacs[0]);
+        assert_eq!(0x94, sacs[1].0);
+        assert_eq!(
+            vec![(0xA0, 0xC3, "Asia".to_owned(), "Reserved".to_owned())],
+            ranges
+        );
+    }
+
+    #[test]
+    fn test_to_rust_compiles() {
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let code = to_rust(&sample(), date).unwrap();
+        assert!(code.contains("pub const RETRIEVED_AT: &str = \"2023-04-01T12:00:00+00:00\";"));
+
+        // Compile the generated code with a small test program and run it
+        //
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("main.rs");
+        fs::write(
+            &src,
+            format!(
+                "{code}\nfn main() {{\n\
+                assert_eq!(Some((\"Asia\", \"Vietnam \\\"south\\\"\")), lookup(0x94));\n\
+                assert_eq!(Some((\"Asia\", \"Reserved\")), lookup(0xB0));\n\
+                assert_eq!(None, lookup(0x00));\n}}\n"
+            ),
+        )
+        .unwrap();
+
+        let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+        let out = Command::new(rustc)
+            .args(["--edition", "2021", "-o"])
+            .arg(dir.path().join("main"))
+            .arg(&src)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+
+        let out = Command::new(dir.path().join("main")).output().unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+
+    #[test]
+    fn test_c_string() {
+        assert_eq!(r#""a \"b\" \\ c\?""#, c_string(r#"a "b" \ c?"#));
+        assert_eq!(r#""\303\251""#, c_string("é"));
+    }
+
+    #[rstest]
+    #[case("Asia/Pacific", 0x94, "SAC_ASIA_PACIFIC_94")]
+    #[case("Côte d'Ivoire", 0x0A, "SAC_COTE_D_IVOIRE_0A")]
+    #[case(" Ελλάδα ", 0x10, "SAC_10")]
+    #[case("Asia & Pacific (1)", 0x01, "SAC_ASIA_PACIFIC_1_01")]
+    fn test_c_enum_name(#[case] area: &str, #[case] sac: u8, #[case] res: &str) {
+        assert_eq!(res, c_enum_name(area, sac));
+    }
+
+    #[test]
+    fn test_c_enum_names_dedup() {
+        let sacs = vec![
+            (0x94, "Asia".to_owned(), "a".to_owned()),
+            (0x94, "Asia".to_owned(), "b".to_owned()),
+            (0x94, "ASIA".to_owned(), "c".to_owned()),
+        ];
+        assert_eq!(
+            vec!["SAC_ASIA_94", "SAC_ASIA_94_2", "SAC_ASIA_94_3"],
+            c_enum_names(&sacs)
+        );
+    }
+
+    /// Compile the header with a small test program and run it, skipped without a C compiler
+    ///
+    fn compile_c(code: &str, body: &str) {
+        let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+        if Command::new(&cc).arg("--version").output().is_err() {
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("sac.h"), code).unwrap();
+        let src = dir.path().join("main.c");
+        fs::write(
+            &src,
+            format!(
+                "#include <assert.h>\n#include <string.h>\n#include \"sac.h\"\n\n\
+                int main(void)\n{{\n{body}\n    return 0;\n}}\n"
+            ),
+        )
+        .unwrap();
+
+        let out = Command::new(&cc)
+            .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-o"])
+            .arg(dir.path().join("main"))
+            .arg(&src)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+
+        let out = Command::new(dir.path().join("main")).output().unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+
+    #[test]
+    fn test_to_c_compiles() {
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let code = to_c(&sample(), date).unwrap();
+        assert!(code.contains("SAC_ASIA_94 = 0x94,"));
+
+        compile_c(
+            &code,
+            r#"    assert(strcmp(sac_name(SAC_ASIA_94), "Vietnam \"south\"") == 0);
+    assert(strcmp(sac_area(0x10), "Europe") == 0);
+    assert(strcmp(sac_name(0xB0), "Reserved") == 0);
+    assert(sac_name(0x00) == NULL);
+    assert(SAC_TABLE_LEN == 3);"#,
+        );
+    }
+
+    #[test]
+    fn test_to_c_odd_names_compile() {
+        let mut a = Area::new("Côte d'Ivoire");
+        a.add("0A", "One");
+        a.add("0a", "Same code");
+        let mut b = Area::new("Côte d’Ivoire");
+        b.add("0A", "Other area, same name");
+        let code = to_c(&[a, b], Utc::now()).unwrap();
+
+        compile_c(
+            &code,
+            r#"    assert(SAC_COTE_D_IVOIRE_0A_3 == 0x0A);
+    assert(SAC_TABLE_LEN == 3);"#,
+        );
+    }
+
+    #[test]
+    fn test_to_c_empty_compiles() {
+        let code = to_c(&[], Utc::now()).unwrap();
+        assert!(!code.contains("enum sac_code"));
+
+        compile_c(
+            &code,
+            r#"    assert(SAC_TABLE_LEN == 0);
+    assert(sac_name(0x94) == NULL);"#,
+        );
+    }
+
+    #[test]
+    fn test_to_python_imports() {
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let code = to_python(&sample(), date).unwrap();
+        assert!(code.contains("    0x94: (\"Asia\", \"Vietnam \\\"south\\\"\"),"));
+
+        // Import the module and use it, skipped without python3
+        //
+        if Command::new("python3").arg("--version").output().is_err() {
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("sac.py"), code).unwrap();
+
+        let out = Command::new("python3")
+            .current_dir(dir.path())
+            .args([
+                "-c",
+                r#"import sac
+assert sac.RETRIEVED_AT == "2023-04-01T12:00:00+00:00"
+assert sac.lookup(0x94) == ("Asia", 'Vietnam "south"')
+assert sac.sac_name(0xB0) == "Reserved"
+assert sac.sac_area(0x10) == "Europe"
+assert sac.lookup(0x00) is None
+"#,
+            ])
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+}
@@ -6,8 +6,9 @@ use csv::{QuoteStyle, WriterBuilder};
 use log::{debug, trace};
 use serde::Serialize;
 use std::collections::VecDeque;
+use std::str::FromStr;
 
-use crate::core::Area;
+use crate::core::{Area, SAC};
 
 /// Take the array with all areas and generate a vector of lines with
 /// 1. header names
@@ -67,3 +68,246 @@ where
     let data = String::from_utf8(wtr.into_inner()?)?;
     Ok(data)
 }
+
+/// Columns available in CSV output
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Column {
+    /// Name of the area
+    Region,
+    /// Area name as a slug, see [`Area::slug`]
+    Slug,
+    /// Code as published, either hex or a range
+    Sac,
+    /// Row in the table on the page, starting at 1
+    Row,
+    /// Decimal value, empty for ranges
+    Decimal,
+    /// Binary representation
+    Binary,
+    /// Lower bound in hex, the code itself if not a range
+    Lo,
+    /// Upper bound in hex, the code itself if not a range
+    Hi,
+    /// Label
+    Label,
+}
+
+impl Column {
+    /// Name used in the header line
+    ///
+    pub fn header(&self) -> &'static str {
+        match self {
+            Column::Region => "Region",
+            Column::Slug => "Slug",
+            Column::Sac => "SAC",
+            Column::Row => "Row",
+            Column::Decimal => "Decimal",
+            Column::Binary => "Binary",
+            Column::Lo => "Lo",
+            Column::Hi => "Hi",
+            Column::Label => "Label",
+        }
+    }
+
+    /// Value for one code
+    ///
+    fn value(&self, area: &Area, code: &str, label: &str) -> String {
+        let sac = SAC::try_from(code).unwrap_or_default();
+        let bounds = match &sac {
+            SAC::Range { lo, hi } => Some((*lo, *hi)),
+            SAC::Hex(s) => usize::from_str_radix(s, 16).ok().map(|v| (v, v)),
+            SAC::Empty => None,
+        };
+        match self {
+            Column::Region => area.name(),
+            Column::Slug => area.slug(),
+            Column::Sac => code.to_owned(),
+            Column::Row => area.row(code).map(|r| r.to_string()).unwrap_or_default(),
+            Column::Decimal => match &sac {
+                SAC::Hex(s) => u8::from_str_radix(s, 16)
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+                _ => String::new(),
+            },
+            Column::Binary => sac.binary().unwrap_or_default(),
+            Column::Lo => bounds
+                .map(|(lo, _)| format!("{:02X}", lo))
+                .unwrap_or_default(),
+            Column::Hi => bounds
+                .map(|(_, hi)| format!("{:02X}", hi))
+                .unwrap_or_default(),
+            Column::Label => label.to_owned(),
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    /// Column names are case-insensitive
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "region" => Ok(Column::Region),
+            "slug" => Ok(Column::Slug),
+            "sac" => Ok(Column::Sac),
+            "row" => Ok(Column::Row),
+            "decimal" => Ok(Column::Decimal),
+            "binary" => Ok(Column::Binary),
+            "lo" => Ok(Column::Lo),
+            "hi" => Ok(Column::Hi),
+            "label" => Ok(Column::Label),
+            _ => Err(format!("unknown column {}", s)),
+        }
+    }
+}
+
+/// When to quote fields
+///
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Quoting {
+    /// Every field, the historical behaviour
+    #[default]
+    Always,
+    /// Only when needed
+    Necessary,
+    /// Every field which is not a number
+    NonNumeric,
+    /// Never, beware of delimiters inside fields
+    Never,
+}
+
+impl From<Quoting> for QuoteStyle {
+    fn from(value: Quoting) -> Self {
+        match value {
+            Quoting::Always => QuoteStyle::Always,
+            Quoting::Necessary => QuoteStyle::Necessary,
+            Quoting::NonNumeric => QuoteStyle::NonNumeric,
+            Quoting::Never => QuoteStyle::Never,
+        }
+    }
+}
+
+impl FromStr for Quoting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "always" => Ok(Quoting::Always),
+            "necessary" => Ok(Quoting::Necessary),
+            "non-numeric" => Ok(Quoting::NonNumeric),
+            "never" => Ok(Quoting::Never),
+            _ => Err(format!("unknown quoting {}", s)),
+        }
+    }
+}
+
+/// CSV dialect and columns, the default is what `to_csv(prepare_data())` generates
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CsvOptions {
+    /// Field delimiter, use `b'\t'` for TSV
+    pub delimiter: u8,
+    /// When to quote fields
+    pub quoting: Quoting,
+    /// Whether we want the header line
+    pub header: bool,
+    /// Which columns, in order
+    pub columns: Vec<Column>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            quoting: Quoting::Always,
+            header: true,
+            columns: vec![Column::Region, Column::Sac, Column::Label],
+        }
+    }
+}
+
+/// Output csv with the given dialect and columns
+///
+pub fn to_csv_with(areas: &[Area], opts: &CsvOptions) -> Result<String> {
+    trace!("Generating csv output with {:?}…", opts);
+    let mut wtr = WriterBuilder::new()
+        .delimiter(opts.delimiter)
+        .quote_style(opts.quoting.into())
+        .from_writer(vec![]);
+
+    if opts.header {
+        wtr.write_record(opts.columns.iter().map(|c| c.header()))?;
+    }
+    for area in areas {
+        for (code, label) in area {
+            wtr.write_record(opts.columns.iter().map(|c| c.value(area, code, label)))?;
+        }
+    }
+
+    let data = String::from_utf8(wtr.into_inner()?)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn sample() -> Vec<Area> {
+        let mut a = Area::new("Asia Pacific");
+        a.add("94", "Vietnam");
+        a.add("A0...C3", "Reserved");
+        vec![a]
+    }
+
+    #[test]
+    fn test_to_csv_with_default() {
+        let old = to_csv(prepare_data(&sample()).unwrap()).unwrap();
+        let new = to_csv_with(&sample(), &CsvOptions::default()).unwrap();
+        assert_eq!(old, new);
+    }
+
+    #[test]
+    fn test_to_csv_with_tsv() {
+        let opts = CsvOptions {
+            delimiter: b'\t',
+            quoting: Quoting::Necessary,
+            header: false,
+            columns: vec![Column::Slug, Column::Decimal, Column::Lo, Column::Hi],
+        };
+        let out = to_csv_with(&sample(), &opts).unwrap();
+        assert_eq!("asia-pacific\t148\t94\t94\nasia-pacific\t\tA0\tC3\n", out);
+    }
+
+    #[test]
+    fn test_to_csv_with_columns() {
+        let opts = CsvOptions {
+            quoting: Quoting::Never,
+            columns: vec![Column::Label, Column::Binary],
+            ..CsvOptions::default()
+        };
+        let out = to_csv_with(&sample(), &opts).unwrap();
+        assert_eq!(
+            "Label,Binary\nVietnam,10010100\nReserved,10100000...11000011\n",
+            out
+        );
+    }
+
+    #[rstest]
+    #[case("region", Column::Region)]
+    #[case("SAC", Column::Sac)]
+    #[case("Decimal", Column::Decimal)]
+    #[case("hi", Column::Hi)]
+    #[case("Row", Column::Row)]
+    fn test_column_from_str(#[case] s: &str, #[case] col: Column) {
+        assert_eq!(col, s.parse().unwrap())
+    }
+
+    #[test]
+    fn test_column_from_str_bad() {
+        assert!("foo".parse::<Column>().is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Module with the Data Source Identifier, the SAC/SIC pair found in item I0xx/010 of most
+//! ASTERIX categories
+//!
+//! The SAC (System Area Code) is the one published by ECTL, the SIC (System Identification
+//! Code) is assigned by each ANSP.  On the wire, it is two bytes, SAC first.
+//!
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{Area, SAC};
+
+/// SAC/SIC pair
+///
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct DataSourceId {
+    /// System Area Code
+    pub sac: u8,
+    /// System Identification Code
+    pub sic: u8,
+}
+
+impl DataSourceId {
+    /// Create new instance
+    ///
+    pub fn new(sac: u8, sic: u8) -> Self {
+        DataSourceId { sac, sic }
+    }
+
+    /// Both bytes as found in a record
+    ///
+    pub fn to_bytes(&self) -> [u8; 2] {
+        [self.sac, self.sic]
+    }
+
+    /// Decimal form, e.g. "148/1"
+    ///
+    pub fn decimal(&self) -> String {
+        format!("{}/{}", self.sac, self.sic)
+    }
+
+    /// Parse the decimal form given by [`DataSourceId::decimal`] or `{:#}`, e.g. "148/1".
+    /// "12/13" is read as decimal here while [`str::parse`] reads it as hex, the caller has to
+    /// know which form it has.
+    ///
+    pub fn from_decimal(s: &str) -> Result<Self, String> {
+        parse_with(s, |p| p.parse::<u8>().ok())
+    }
+
+    /// Find the area and label of the SAC part in the scraped list, single codes are looked
+    /// up first then ranges
+    ///
+    pub fn resolve<'a>(&self, areas: &'a [Area]) -> Option<(&'a Area, &'a str)> {
+        lookup_sac(areas, self.sac)
+    }
+}
+
+/// Find the area and label of a SAC given as a value, single codes win over ranges
+///
+pub fn lookup_sac(areas: &[Area], sac: u8) -> Option<(&Area, &str)> {
+    let find = |single: bool| {
+        areas.iter().find_map(|area| {
+            area.iter()
+                .find(
+                    |(code, _)| match SAC::try_from(code.as_str()).unwrap_or_default() {
+                        SAC::Hex(s) if single => u8::from_str_radix(&s, 16) == Ok(sac),
+                        SAC::Range { lo, hi } if !single => (lo..=hi).contains(&(sac as usize)),
+                        _ => false,
+                    },
+                )
+                .map(|(_, label)| (area, label.as_str()))
+        })
+    };
+    find(true).or_else(|| find(false))
+}
+
+impl Display for DataSourceId {
+    /// Hex by default, e.g. "94/01", decimal with `{:#}`, e.g. "148/1"
+    ///
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            write!(f, "{}", self.decimal())
+        } else {
+            write!(f, "{:02X}/{:02X}", self.sac, self.sic)
+        }
+    }
+}
+
+/// Split "SAC/SIC" and read both parts with `code`
+///
+fn parse_with(s: &str, code: impl Fn(&str) -> Option<u8>) -> Result<DataSourceId, String> {
+    let part = |p: &str| {
+        let p = p.trim();
+        code(p).ok_or_else(|| format!("invalid code {} in {}", p, s))
+    };
+
+    match s.split_once('/') {
+        Some((sac, sic)) => Ok(DataSourceId::new(part(sac)?, part(sic)?)),
+        None => Err(format!("{} is not SAC/SIC", s)),
+    }
+}
+
+impl FromStr for DataSourceId {
+    type Err = String;
+
+    /// Parse the hex form given by `Display`, e.g. "94/0A", like the SAC list.  A `0x` prefix
+    /// is allowed, see [`DataSourceId::from_decimal`] for the decimal form.
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_with(s, |p| {
+            let h = p
+                .strip_prefix("0x")
+                .or_else(|| p.strip_prefix("0X"))
+                .unwrap_or(p);
+            u8::from_str_radix(h, 16).ok()
+        })
+    }
+}
+
+impl From<[u8; 2]> for DataSourceId {
+    /// SAC first, as on the wire
+    ///
+    fn from(value: [u8; 2]) -> Self {
+        DataSourceId::new(value[0], value[1])
+    }
+}
+
+impl From<u16> for DataSourceId {
+    /// SAC in the high byte
+    ///
+    fn from(value: u16) -> Self {
+        DataSourceId::from(value.to_be_bytes())
+    }
+}
+
+impl From<DataSourceId> for u16 {
+    fn from(value: DataSourceId) -> Self {
+        u16::from_be_bytes(value.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("94/01", DataSourceId::new(0x94, 1))]
+    #[case("0x94/0x0A", DataSourceId::new(0x94, 10))]
+    #[case(" 94 / 0a ", DataSourceId::new(0x94, 10))]
+    fn test_dsi_from_str(#[case] s: &str, #[case] res: DataSourceId) {
+        assert_eq!(res, s.parse().unwrap());
+    }
+
+    #[rstest]
+    #[case("94")]
+    #[case("94/1FF")]
+    #[case("#94/01")]
+    #[case("zz/01")]
+    fn test_dsi_from_str_bad(#[case] s: &str) {
+        assert!(s.parse::<DataSourceId>().is_err());
+    }
+
+    #[rstest]
+    #[case("148/10", Ok(DataSourceId::new(0x94, 10)))]
+    #[case(" 12 / 13 ", Ok(DataSourceId::new(12, 13)))]
+    #[case("256/1", Err("invalid code 256 in 256/1".to_owned()))]
+    #[case("94/0A", Err("invalid code 0A in 94/0A".to_owned()))]
+    #[case("148", Err("148 is not SAC/SIC".to_owned()))]
+    fn test_dsi_from_decimal(#[case] s: &str, #[case] res: Result<DataSourceId, String>) {
+        assert_eq!(res, DataSourceId::from_decimal(s));
+    }
+
+    #[test]
+    fn test_dsi_conversions() {
+        let dsi = DataSourceId::from([0x94, 0x01]);
+
+        assert_eq!(DataSourceId::new(0x94, 1), dsi);
+        assert_eq!(dsi, DataSourceId::from(0x9401u16));
+        assert_eq!(0x9401u16, u16::from(dsi));
+        assert_eq!([0x94, 0x01], dsi.to_bytes());
+    }
+
+    #[test]
+    fn test_dsi_display() {
+        let dsi = DataSourceId::new(0x94, 10);
+
+        assert_eq!("94/0A", dsi.to_string());
+        assert_eq!("148/10", format!("{:#}", dsi));
+    }
+
+    #[test]
+    fn test_dsi_roundtrip() {
+        for v in 0..=u16::MAX {
+            let dsi = DataSourceId::from(v);
+            assert_eq!(Ok(dsi), dsi.to_string().parse());
+            assert_eq!(Ok(dsi), DataSourceId::from_decimal(&format!("{:#}", dsi)));
+        }
+    }
+
+    #[test]
+    fn test_dsi_resolve() {
+        let mut eu = Area::new("Europe");
+        eu.add("00", "Local").add("A0...C3", "Reserved");
+        let mut asia = Area::new("Asia");
+        asia.add("94", "Vietnam").add("A1", "Somewhere");
+        let areas = vec![eu, asia];
+
+        let (area, label) = DataSourceId::new(0x94, 1).resolve(&areas).unwrap();
+        assert_eq!(("Asia", "Vietnam"), (area.name().as_str(), label));
+        let (_, label) = DataSourceId::new(0xA1, 1).resolve(&areas).unwrap();
+        assert_eq!("Somewhere", label);
+        let (_, label) = DataSourceId::new(0xA2, 1).resolve(&areas).unwrap();
+        assert_eq!("Reserved", label);
+        assert!(DataSourceId::new(0x10, 1).resolve(&areas).is_none());
+    }
+}
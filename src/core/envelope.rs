@@ -0,0 +1,101 @@
+//! Module defining the `Envelope`, the list of areas along with metadata telling consumers
+//! which dataset they hold.
+//!
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::core::{Area, Order, PAGE};
+
+/// Current version of the envelope format, bump when the layout changes in a way older readers
+/// can not follow.  New optional fields with a default, like the page order of the area rows,
+/// the area notes or `page_updated`, keep the version as files without them still load.
+///
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// All areas with where and when they were retrieved
+///
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Envelope {
+    /// Version of this format
+    pub schema_version: u32,
+    /// Where the data comes from
+    pub source_url: String,
+    /// When the data was retrieved
+    pub retrieved_at: DateTime<Utc>,
+    /// Last update of the page itself, as published
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_updated: Option<String>,
+    /// `sha256:` followed by the hash of the compact JSON of `areas`
+    pub hash: String,
+    /// The data itself
+    pub areas: Vec<Area>,
+}
+
+impl Envelope {
+    /// Wrap `areas` retrieved from the official page at `retrieved`
+    ///
+    pub fn new(areas: &[Area], retrieved: DateTime<Utc>) -> Result<Self> {
+        Ok(Envelope {
+            schema_version: SCHEMA_VERSION,
+            source_url: PAGE.to_owned(),
+            retrieved_at: retrieved,
+            page_updated: None,
+            hash: content_hash(areas)?,
+            areas: areas.to_vec(),
+        })
+    }
+}
+
+/// Hash of the content, only depends on the data and not on when it was retrieved or how the
+/// codes are sorted
+///
+pub fn content_hash(areas: &[Area]) -> Result<String> {
+    let mut areas = areas.to_vec();
+    areas.iter_mut().for_each(|a| a.sort(Order::Page));
+    let data = serde_json::to_vec(&areas)?;
+    Ok(format!("sha256:{:x}", Sha256::digest(data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_new() {
+        let mut a = Area::new("Asia");
+        a.add("94", "Vietnam");
+        let env = Envelope::new(&[a.clone()], Utc::now()).unwrap();
+
+        assert_eq!(SCHEMA_VERSION, env.schema_version);
+        assert_eq!(None, env.page_updated);
+        assert_eq!(PAGE, env.source_url);
+        assert_eq!(1, env.areas.len());
+        assert!(env.hash.starts_with("sha256:"));
+        assert_eq!(7 + 64, env.hash.len());
+        assert_eq!(env.hash, content_hash(&[a.clone()]).unwrap());
+
+        a.add("01", "Bar");
+        assert_ne!(env.hash, content_hash(&[a.clone()]).unwrap());
+
+        let hash = content_hash(&[a.clone()]).unwrap();
+        a.sort(Order::Hex);
+        assert_eq!(hash, content_hash(&[a]).unwrap());
+    }
+
+    #[test]
+    fn test_envelope_without_optional_fields() {
+        let s = r#"{"schema_version":1,"source_url":"https://www.eurocontrol.int/asterix",
+            "retrieved_at":"2024-01-01T00:00:00Z","hash":"sha256:",
+            "areas":[{"label":"Asia","list":{"94":"Vietnam","01":"Bar"}}]}"#;
+        let env: Envelope = serde_json::from_str(s).unwrap();
+
+        assert_eq!(SCHEMA_VERSION, env.schema_version);
+        assert_eq!(None, env.page_updated);
+        assert_eq!(Some(2), env.areas[0].row("01"));
+        assert!(env.areas[0].notes().is_empty());
+    }
+}
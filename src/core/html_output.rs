@@ -0,0 +1,175 @@
+//! Module dealing with the standalone HTML report
+//!
+//! Everything (style and the small filtering script) is inlined so the file can be published
+//! as-is.
+//!
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use log::trace;
+
+use crate::core::markdown_output::unique;
+use crate::core::{Area, PAGE, SAC};
+
+pub(crate) const STYLE: &str = r##"
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.8em; text-align: left; }
+th { background: #eee; }
+#filter { margin-bottom: 1em; padding: 0.3em; width: 20em; }
+footer { color: #666; font-size: small; }
+"##;
+
+const SCRIPT: &str = r##"
+document.getElementById("filter").addEventListener("input", function (ev) {
+  const q = ev.target.value.toLowerCase();
+  document.querySelectorAll("section.area").forEach(function (sect) {
+    let shown = 0;
+    sect.querySelectorAll("tbody tr").forEach(function (tr) {
+      const ok = tr.textContent.toLowerCase().includes(q);
+      tr.style.display = ok ? "" : "none";
+      if (ok) { shown++; }
+    });
+    sect.style.display = shown > 0 ? "" : "none";
+  });
+});
+"##;
+
+/// Ids of the sections, the slugs made unique like the Markdown anchors, "area" standing for
+/// names without any letter or digit
+///
+fn ids(areas: &[Area]) -> Vec<String> {
+    unique(areas.iter().map(|a| match a.slug() {
+        s if s.is_empty() => "area".to_owned(),
+        s => s,
+    }))
+}
+
+/// Generate a standalone HTML page with one table per area, a search box and a footer
+/// with the retrieval date and the last update of the page if known.
+///
+pub fn to_html(areas: &[Area], retrieved: DateTime<Utc>, updated: Option<&str>) -> Result<String> {
+    trace!("Generating html output…");
+
+    let ids = ids(areas);
+    let toc = areas
+        .iter()
+        .zip(&ids)
+        .map(|(a, id)| {
+            format!(
+                "<li><a href=\"#{}\">{}</a></li>",
+                encode_double_quoted_attribute(id),
+                encode_text(&a.name())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let sections = areas
+        .iter()
+        .zip(&ids)
+        .map(|(a, id)| {
+            let rows = a
+                .iter()
+                .map(|(code, label)| {
+                    let bin = SAC::try_from(code.as_str())
+                        .unwrap_or_default()
+                        .binary()
+                        .unwrap_or_default();
+                    format!(
+                        "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                        encode_text(code),
+                        encode_text(label),
+                        bin
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let notes = a
+                .notes()
+                .iter()
+                .map(|n| format!("<p class=\"note\">{}</p>\n", encode_text(n)))
+                .collect::<String>();
+            format!(
+                "<section class=\"area\" id=\"{}\">\n<h2>{}</h2>\n<table>\n\
+                <thead><tr><th>SAC</th><th>Label</th><th>Binary</th></tr></thead>\n\
+                <tbody>\n{}\n</tbody>\n</table>\n{}</section>",
+                encode_double_quoted_attribute(id),
+                encode_text(&a.name()),
+                rows,
+                notes
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>SAC codes</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+        <h1>SAC codes</h1>\n<ul>\n{toc}\n</ul>\n\
+        <input id=\"filter\" type=\"search\" placeholder=\"Filter…\">\n\
+        {sections}\n\
+        <footer>Retrieved on {} from <a href=\"{PAGE}\">ECTL</a>{}.</footer>\n\
+        <script>{SCRIPT}</script>\n</body>\n</html>\n",
+        retrieved.format("%Y-%m-%d %H:%M:%S UTC"),
+        updated
+            .map(|u| format!(", page last updated on {}", encode_text(u)))
+            .unwrap_or_default(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_to_html() {
+        let mut a = Area::new("Asia & Pacific");
+        a.add("94", "Vietnam <south>");
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+
+        a.add_note("Codes <80> are reserved");
+        let html = to_html(&[a.clone()], date, None).unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<li><a href=\"#asia-pacific\">Asia &amp; Pacific</a></li>"));
+        assert!(html.contains("<section class=\"area\" id=\"asia-pacific\">"));
+        assert!(
+            html.contains("<tr><td>94</td><td>Vietnam &lt;south&gt;</td><td>10010100</td></tr>")
+        );
+        assert!(html.contains("<footer>Retrieved on 2023-04-01 12:00:00 UTC"));
+        assert!(html.contains("id=\"filter\""));
+        assert!(html.contains("<p class=\"note\">Codes &lt;80&gt; are reserved</p>"));
+
+        let html = to_html(&[a], date, Some("12 March 2023")).unwrap();
+        assert!(html.contains("</a>, page last updated on 12 March 2023.</footer>"));
+    }
+
+    #[test]
+    fn test_to_html_ids() {
+        let areas = [
+            Area::new("Asia & Pacific"),
+            Area::new("Asia/Pacific"),
+            Area::new("***"),
+            Area::new("---"),
+        ];
+        let html = to_html(&areas, Utc::now(), None).unwrap();
+
+        for id in ["asia-pacific", "asia-pacific-1", "area", "area-1"] {
+            assert!(
+                html.contains(&format!("<li><a href=\"#{}\">", id)),
+                "{}",
+                id
+            );
+            assert!(
+                html.contains(&format!("<section class=\"area\" id=\"{}\">", id)),
+                "{}",
+                id
+            );
+        }
+        assert!(!html.contains("id=\"\""));
+    }
+}
@@ -0,0 +1,224 @@
+//! Module dealing with reading back what we generated earlier
+//!
+//! That way saved files can be used instead of fetching the page again.
+//!
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use csv::ReaderBuilder;
+use log::{debug, info};
+use serde::Deserialize;
+
+use crate::core::{
+    from_bincode, from_cbor, from_msgpack, from_protobuf, Area, Column, Envelope, SAC,
+};
+
+/// Check that every code is either a hex byte or a range
+///
+pub fn validate(areas: &[Area]) -> Result<()> {
+    for area in areas {
+        for (code, _) in area {
+            SAC::try_from(code.as_str()).map_err(|e| anyhow!("{}: {}", area.name(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Guess the delimiter from the header line: the most frequent of `,`, tab, `;` and `|`
+///
+fn delimiter(data: &str) -> u8 {
+    let hdr = data.lines().next().unwrap_or_default();
+    [b',', b'\t', b';', b'|']
+        .into_iter()
+        .max_by_key(|d| hdr.bytes().filter(|b| b == d).count())
+        .unwrap_or(b',')
+}
+
+/// Read back the CSV from `to_csv(prepare_data())` or `to_csv_with()`
+///
+/// The delimiter is guessed and columns are found by name so any `--delimiter`, `--quote` and
+/// `--columns` work as long as the header is there with at least `Region`, `SAC` and `Label`.
+/// Areas are kept in the order of their first appearance.
+///
+pub fn from_csv(data: &str) -> Result<Vec<Area>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(delimiter(data))
+        .from_reader(data.as_bytes());
+
+    let hdr = rdr.headers()?.clone();
+    let find = |col: Column| {
+        hdr.iter()
+            .position(|h| h.parse::<Column>() == Ok(col))
+            .ok_or_else(|| anyhow!("bad csv header {:?}, no {} column", hdr, col.header()))
+    };
+    let (c_region, c_code, c_label) = (
+        find(Column::Region)?,
+        find(Column::Sac)?,
+        find(Column::Label)?,
+    );
+
+    let mut areas: Vec<Area> = vec![];
+    for rec in rdr.records() {
+        let rec = rec?;
+        let field = |i: usize| rec.get(i).unwrap_or_default().to_owned();
+        let (region, code, label) = (field(c_region), field(c_code), field(c_label));
+        let idx = match areas.iter().position(|a| a.name() == region) {
+            Some(idx) => idx,
+            None => {
+                areas.push(Area::new(&region));
+                areas.len() - 1
+            }
+        };
+        areas[idx].add(&code, &label);
+    }
+    validate(&areas)?;
+    Ok(areas)
+}
+
+/// What we accept as JSON
+///
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Json {
+    Areas(Vec<Area>),
+    Envelope(Envelope),
+}
+
+/// Read back JSON, either the list of areas from `-F json` or a JSON envelope
+///
+pub fn from_json(data: &str) -> Result<Vec<Area>> {
+    let areas = match serde_json::from_str(data)? {
+        Json::Areas(areas) => areas,
+        Json::Envelope(env) => env.areas,
+    };
+    validate(&areas)?;
+    Ok(areas)
+}
+
+/// Load a file saved earlier, the format is guessed from the extension.
+///
+/// For formats without metadata, the modification time of the file is used as retrieval date.
+/// Every code is checked, see [`validate`].
+///
+pub fn load_file(path: &Path) -> Result<Envelope> {
+    info!("Reading {}...", path.to_string_lossy());
+    let data = fs::read(path)?;
+    let mtime: DateTime<Utc> = fs::metadata(path)?.modified()?.into();
+
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    debug!("ext={}", ext);
+    let areas = match ext {
+        "pb" => {
+            let env = from_protobuf(&data)?;
+            validate(&env.areas)?;
+            return Ok(env);
+        }
+        "json" => {
+            let data = String::from_utf8(data)?;
+            if let Ok(env) = serde_json::from_str::<Envelope>(&data) {
+                validate(&env.areas)?;
+                return Ok(env);
+            }
+            from_json(&data)?
+        }
+        "csv" | "tsv" => from_csv(&String::from_utf8(data)?)?,
+        "msgpack" | "mpk" => from_msgpack(&data)?,
+        "cbor" => from_cbor(&data)?,
+        "bincode" | "bin" => from_bincode(&data)?,
+        _ => {
+            return Err(anyhow!(
+                "unknown input format for {}",
+                path.to_string_lossy()
+            ))
+        }
+    };
+    validate(&areas)?;
+    Envelope::new(&areas, mtime)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::core::{prepare_data, to_csv, to_csv_with, to_json_envelope, to_msgpack};
+    use crate::core::{CsvOptions, Quoting};
+
+    fn sample() -> Vec<Area> {
+        let mut a = Area::new("Asia");
+        a.add("94", "Vietnam");
+        a.add("A0...C3", "Reserved, really");
+        let mut b = Area::new("Europe");
+        b.add("00", "Nowhere");
+        vec![a, b]
+    }
+
+    #[test]
+    fn test_from_csv() {
+        let data = to_csv(prepare_data(&sample()).unwrap()).unwrap();
+        assert_eq!(sample(), from_csv(&data).unwrap());
+    }
+
+    #[test]
+    fn test_from_csv_options() {
+        let opts = CsvOptions {
+            delimiter: b'\t',
+            quoting: Quoting::Necessary,
+            header: true,
+            columns: vec![Column::Label, Column::Decimal, Column::Sac, Column::Region],
+        };
+        let data = to_csv_with(&sample(), &opts).unwrap();
+        assert_eq!(sample(), from_csv(&data).unwrap());
+
+        let data = "SAC;Region;Label\n94;Asia;Vietnam\n";
+        assert_eq!("Vietnam", from_csv(data).unwrap()[0].get("94").unwrap());
+    }
+
+    #[test]
+    fn test_from_csv_bad_header() {
+        assert!(from_csv("Area,SAC,Label\nfoo,00,bar\n").is_err());
+    }
+
+    #[test]
+    fn test_from_csv_bad_code() {
+        let err = from_csv("Region,SAC,Label\nAsia,ZZ,bar\n").unwrap_err();
+        assert!(err.to_string().contains("Asia: invalid SAC \"ZZ\""));
+        assert!(from_csv("Region,SAC,Label\nAsia,X...Y,bar\n").is_err());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let data = serde_json::to_string(&sample()).unwrap();
+        assert_eq!(sample(), from_json(&data).unwrap());
+
+        let data = to_json_envelope(&sample(), Utc::now()).unwrap();
+        assert_eq!(sample(), from_json(&data).unwrap());
+
+        assert!(from_json("{}").is_err());
+        assert!(from_json(r#"[{"label":"Asia","list":{"ZZ":"bar"}}]"#).is_err());
+    }
+
+    #[test]
+    fn test_load_file() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let path = dir.path().join("sac.json");
+        fs::write(&path, to_json_envelope(&sample(), date).unwrap()).unwrap();
+        let env = load_file(&path).unwrap();
+        assert_eq!(sample(), env.areas);
+        assert_eq!(date, env.retrieved_at);
+
+        let path = dir.path().join("sac.mpk");
+        fs::write(&path, to_msgpack(&sample()).unwrap()).unwrap();
+        assert_eq!(sample(), load_file(&path).unwrap().areas);
+
+        let path = dir.path().join("sac.txt");
+        fs::write(&path, "foo").unwrap();
+        assert!(load_file(&path).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+//! Module dealing with the JSON variants besides the compact one
+//!
+//! - JSON Lines (aka NDJSON), one flat record per code for log pipelines
+//! - pretty-printed JSON wrapped inside an [`Envelope`]
+//!
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::trace;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{Area, Envelope, SAC};
+
+/// One code in a flat record
+///
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Record {
+    /// Name of the area
+    pub area: String,
+    /// Code as published
+    pub sac: String,
+    /// Row in the table on the page, starting at 1
+    pub row: Option<usize>,
+    /// Label
+    pub label: String,
+    /// Binary representation
+    pub binary: Option<String>,
+}
+
+/// Flatten all areas into a list of records
+///
+pub fn to_records(areas: &[Area]) -> Vec<Record> {
+    areas
+        .iter()
+        .flat_map(|area| {
+            area.iter().map(|(code, label)| Record {
+                area: area.name(),
+                sac: code.to_owned(),
+                row: area.row(code),
+                label: label.to_owned(),
+                binary: SAC::try_from(code.as_str()).unwrap_or_default().binary(),
+            })
+        })
+        .collect()
+}
+
+/// Generate JSON Lines, one record per code
+///
+pub fn to_ndjson(areas: &[Area]) -> Result<String> {
+    trace!("Generating ndjson output…");
+    let lines = to_records(areas)
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(lines.join("\n"))
+}
+
+/// Generate pretty-printed JSON with all the metadata
+///
+pub fn to_json_envelope(areas: &[Area], retrieved: DateTime<Utc>) -> Result<String> {
+    to_json_envelope_with(&Envelope::new(areas, retrieved)?)
+}
+
+/// Generate pretty-printed JSON from an existing envelope, e.g. with `page_updated` set
+///
+pub fn to_json_envelope_with(env: &Envelope) -> Result<String> {
+    trace!("Generating json envelope output…");
+    Ok(serde_json::to_string_pretty(env)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::core::PAGE;
+
+    fn sample() -> Vec<Area> {
+        let mut a = Area::new("Asia");
+        a.add("94", "Vietnam");
+        a.add("A0...C3", "Reserved");
+        vec![a]
+    }
+
+    #[test]
+    fn test_to_ndjson() {
+        let out = to_ndjson(&sample()).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+
+        assert_eq!(2, lines.len());
+        assert_eq!(
+            r#"{"area":"Asia","sac":"94","row":1,"label":"Vietnam","binary":"10010100"}"#,
+            lines[0]
+        );
+        let r: Record = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!("A0...C3", r.sac);
+        assert_eq!(Some(2), r.row);
+    }
+
+    #[test]
+    fn test_to_json_envelope() {
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let out = to_json_envelope(&sample(), date).unwrap();
+
+        assert!(out.starts_with("{\n  \"schema_version\": 1,"));
+        let env: Envelope = serde_json::from_str(&out).unwrap();
+        assert_eq!(PAGE, env.source_url);
+        assert_eq!(date, env.retrieved_at);
+        assert_eq!(1, env.areas.len());
+        assert!(!out.contains("page_updated"));
+
+        let mut env = Envelope::new(&sample(), date).unwrap();
+        env.page_updated = Some("12 March 2023".to_owned());
+        let out = to_json_envelope_with(&env).unwrap();
+        assert!(out.contains("\"page_updated\": \"12 March 2023\","));
+    }
+}
@@ -0,0 +1,153 @@
+//! Module dealing with Markdown output, suitable for wikis and generated documentation
+//!
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::trace;
+
+use crate::core::{Area, SAC};
+
+/// Escape what would break a table cell
+///
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Anchor generated by GitHub for a heading: lowercase, punctuation removed and spaces turned
+/// into `-`, so "Asia & Pacific" gives "asia--pacific"
+///
+pub(crate) fn anchor(heading: &str) -> String {
+    heading
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+/// Anchors for all the headings, with `-1`, `-2`… for duplicates like GitHub does
+///
+fn anchors<'a>(headings: impl Iterator<Item = &'a str>) -> Vec<String> {
+    unique(headings.map(anchor))
+}
+
+/// Make anchors unique by adding `-1`, `-2`… to the later duplicates
+///
+pub(crate) fn unique(bases: impl Iterator<Item = String>) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    bases
+        .map(|base| {
+            let n = seen.entry(base.clone()).or_default();
+            let res = match *n {
+                0 => base,
+                n => format!("{}-{}", base, n),
+            };
+            *n += 1;
+            res
+        })
+        .collect()
+}
+
+/// Generate a Markdown document with a table of contents and one table per area, `updated` is
+/// the last update of the page if known
+///
+pub fn to_markdown(
+    areas: &[Area],
+    retrieved: DateTime<Utc>,
+    updated: Option<&str>,
+) -> Result<String> {
+    trace!("Generating markdown output…");
+
+    let names: Vec<_> = areas.iter().map(|a| a.name()).collect();
+    let toc = names
+        .iter()
+        .zip(anchors(names.iter().map(|n| n.as_str())))
+        .map(|(name, anchor)| format!("- [{}](#{})", escape(name), anchor))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let tables = areas
+        .iter()
+        .map(|a| {
+            let rows = a
+                .iter()
+                .map(|(code, label)| {
+                    let bin = SAC::try_from(code.as_str())
+                        .unwrap_or_default()
+                        .binary()
+                        .unwrap_or_default();
+                    format!("| {} | {} | {} |", escape(code), escape(label), bin)
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let notes = a
+                .notes()
+                .iter()
+                .map(|n| format!("\n> {}\n", n.replace('\n', " ")))
+                .collect::<String>();
+            format!(
+                "## {}\n\n| SAC | Label | Binary |\n|-----|-------|--------|\n{}\n{}",
+                escape(&a.name()),
+                rows,
+                notes
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let updated = updated
+        .map(|u| format!(" Page last updated on {}.", u))
+        .unwrap_or_default();
+    Ok(format!(
+        "# SAC codes\n\nRetrieved on {}.{}\n\n{}\n\n{}",
+        retrieved.format("%Y-%m-%d %H:%M:%S UTC"),
+        updated,
+        toc,
+        tables
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn test_to_markdown() {
+        let mut a = Area::new("Asia & Pacific");
+        a.add("94", "Vietnam");
+        a.add("95", "Foo | Bar");
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+
+        a.add_note("Reserved codes");
+        let md = to_markdown(&[a.clone()], date, None).unwrap();
+
+        assert!(md.contains("Retrieved on 2023-04-01 12:00:00 UTC.\n"));
+        assert!(md.contains("- [Asia & Pacific](#asia--pacific)"));
+        assert!(md.contains("## Asia & Pacific\n\n| SAC | Label | Binary |"));
+        assert!(md.contains("| 94 | Vietnam | 10010100 |"));
+        assert!(md.contains("| 95 | Foo \\| Bar | 10010101 |"));
+        assert!(md.contains("\n> Reserved codes\n"));
+
+        let md = to_markdown(&[a.clone(), a.clone()], date, None).unwrap();
+        assert!(md.contains("- [Asia & Pacific](#asia--pacific-1)"));
+
+        let md = to_markdown(&[a], date, Some("12 March 2023")).unwrap();
+        assert!(md.contains(
+            "Retrieved on 2023-04-01 12:00:00 UTC. Page last updated on 12 March 2023.\n"
+        ));
+    }
+
+    #[rstest]
+    #[case("Europe", "europe")]
+    #[case("Asia & Pacific", "asia--pacific")]
+    #[case("North/South America", "northsouth-america")]
+    #[case("Côte d'Ivoire (1)", "côte-divoire-1")]
+    fn test_anchor(#[case] heading: &str, #[case] res: &str) {
+        assert_eq!(res, anchor(heading));
+    }
+}
@@ -0,0 +1,619 @@
+//! Module dealing with a local mirror of the ASTERIX specifications
+//!
+//! Every PDF linked from a category is downloaded into `<dir>/catNNN/` and described in
+//! `<dir>/manifest.json`.  On the next run, the `ETag` and `Last-Modified` recorded in the
+//! manifest are sent back so unchanged documents are not downloaded again.  Comparing the
+//! previous manifest with the new one tells which categories got a new edition.
+//!
+
+#[cfg(feature = "live")]
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "live")]
+use std::path::PathBuf;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+#[cfg(feature = "live")]
+use log::{debug, info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "live")]
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "live")]
+use crate::core::{Category, Link};
+
+/// Name of the manifest inside the mirror
+///
+pub const MANIFEST: &str = "manifest.json";
+
+/// One downloaded document
+///
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MirrorEntry {
+    /// Category number
+    pub category: u8,
+    /// Edition of the category when the document was downloaded
+    pub edition: Option<String>,
+    /// Where it comes from
+    pub url: String,
+    /// Path relative to the mirror directory
+    pub file: String,
+    /// Hex SHA-256 of the content
+    pub sha256: String,
+    /// Size in bytes
+    pub size: u64,
+    /// `ETag` sent by the server, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// `Last-Modified` sent by the server, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+/// Content of `manifest.json`
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Manifest {
+    /// When the mirror was last updated
+    pub updated_at: Option<DateTime<Utc>>,
+    /// All documents, sorted by category then URL
+    pub entries: Vec<MirrorEntry>,
+}
+
+impl Manifest {
+    /// Load the manifest of the mirror in `dir`, empty if there is none yet
+    ///
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST);
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Write the manifest into `dir`
+    ///
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        fs::write(dir.join(MANIFEST), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Entry for `url`
+    ///
+    pub fn get(&self, url: &str) -> Option<&MirrorEntry> {
+        self.entries.iter().find(|e| e.url == url)
+    }
+
+    /// Categories whose edition differs from `old`, with the old and new editions
+    ///
+    pub fn new_editions(&self, old: &Manifest) -> Vec<(u8, Option<String>, Option<String>)> {
+        let mut res: Vec<_> = self
+            .entries
+            .iter()
+            .filter_map(|e| {
+                let before = old.entries.iter().find(|o| o.category == e.category);
+                match before {
+                    Some(o) if o.edition == e.edition => None,
+                    _ => Some((
+                        e.category,
+                        before.and_then(|o| o.edition.clone()),
+                        e.edition.clone(),
+                    )),
+                }
+            })
+            .collect();
+        res.dedup();
+        res
+    }
+}
+
+/// What happened during a run
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MirrorReport {
+    /// Documents written
+    pub downloaded: usize,
+    /// Documents the server said were not modified
+    pub unchanged: usize,
+    /// URLs which could not be downloaded, with the error
+    pub failed: Vec<(String, String)>,
+}
+
+/// Only specifications are mirrored, i.e. PDF documents
+///
+#[cfg(feature = "live")]
+fn is_pdf(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.to_lowercase().ends_with(".pdf")
+}
+
+/// Where a document goes, relative to the mirror: the last segment of the URL unless another
+/// URL already got that file in `taken`, the start of the hash of the URL is then added to the
+/// name to keep them apart.  Names like `..` which would leave the category directory are
+/// replaced by the hash.
+///
+#[cfg(feature = "live")]
+fn local_path(cat: &Category, url: &str, taken: &HashSet<String>) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let name = match name {
+        "." | ".." => "",
+        _ => name,
+    };
+    let file = format!("cat{:03}/{}", cat.number, name);
+    if !name.is_empty() && !taken.contains(&file) {
+        return file;
+    }
+
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+        _ => (name, String::new()),
+    };
+    let hash = &sha256(url.as_bytes())[..8];
+    match stem.is_empty() {
+        true => format!("cat{:03}/{}{}", cat.number, hash, ext),
+        false => format!("cat{:03}/{}-{}{}", cat.number, stem, hash, ext),
+    }
+}
+
+/// Hex SHA-256 of some data
+///
+#[cfg(feature = "live")]
+fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// The file in the mirror still matches the manifest
+///
+#[cfg(feature = "live")]
+fn is_intact(dir: &Path, entry: &MirrorEntry) -> bool {
+    fs::read(dir.join(&entry.file))
+        .map(|data| sha256(&data) == entry.sha256)
+        .unwrap_or(false)
+}
+
+/// Write a document into the mirror, creating its directory
+///
+#[cfg(feature = "live")]
+fn store(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, data)
+}
+
+/// Download every PDF linked from `cats` into `dir` and update the manifest there
+///
+#[cfg(feature = "live")]
+pub fn mirror(cats: &[Category], dir: &Path) -> Result<(Manifest, MirrorReport)> {
+    use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+    use reqwest::StatusCode;
+
+    fs::create_dir_all(dir)?;
+    let old = Manifest::load(dir)?;
+    let client = reqwest::blocking::Client::builder()
+        .user_agent(concat!("fetch-sac/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+
+    let mut manifest = Manifest::default();
+    let mut report = MirrorReport::default();
+    let mut taken = HashSet::new();
+
+    let docs = cats
+        .iter()
+        .flat_map(|c| c.links.iter().map(move |l| (c, l)))
+        .filter(|(_, l)| is_pdf(&l.url));
+    for (cat, Link { url, .. }) in docs {
+        if manifest.get(url).is_some() {
+            continue;
+        }
+        let file = local_path(cat, url, &taken);
+        taken.insert(file.clone());
+
+        // Only use the cache if we still have the very same file
+        //
+        let cached = old.get(url).filter(|e| e.file == file && is_intact(dir, e));
+        let mut req = client.get(url);
+        if let Some(e) = cached {
+            if let Some(etag) = &e.etag {
+                req = req.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(lm) = &e.last_modified {
+                req = req.header(IF_MODIFIED_SINCE, lm);
+            }
+        }
+
+        let resp = match req.send().and_then(|r| r.error_for_status()) {
+            Ok(resp) => resp,
+            Err(e) => {
+                warn!("{}: {}", url, e);
+                report.failed.push((url.to_owned(), e.to_string()));
+                if let Some(e) = cached {
+                    manifest.entries.push(e.clone());
+                }
+                continue;
+            }
+        };
+
+        if let (StatusCode::NOT_MODIFIED, Some(e)) = (resp.status(), cached) {
+            debug!("{} not modified", url);
+            report.unchanged += 1;
+            manifest.entries.push(MirrorEntry {
+                category: cat.number,
+                edition: cat.edition.clone(),
+                ..e.clone()
+            });
+            continue;
+        }
+
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_owned())
+        };
+        let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+        let data = match resp.bytes() {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("{}: {}", url, e);
+                report.failed.push((url.to_owned(), e.to_string()));
+                if let Some(e) = cached {
+                    manifest.entries.push(e.clone());
+                }
+                continue;
+            }
+        };
+
+        let path: PathBuf = dir.join(&file);
+        if let Err(e) = store(&path, &data) {
+            warn!("{}: {}", path.to_string_lossy(), e);
+            report.failed.push((url.to_owned(), e.to_string()));
+            if let Some(e) = cached {
+                manifest.entries.push(e.clone());
+            }
+            continue;
+        }
+        info!("{} -> {}", url, path.to_string_lossy());
+        report.downloaded += 1;
+
+        manifest.entries.push(MirrorEntry {
+            category: cat.number,
+            edition: cat.edition.clone(),
+            url: url.to_owned(),
+            file,
+            sha256: sha256(&data),
+            size: data.len() as u64,
+            etag,
+            last_modified,
+        });
+    }
+
+    manifest
+        .entries
+        .sort_by(|a, b| (a.category, &a.url).cmp(&(b.category, &b.url)));
+    manifest.updated_at = Some(Utc::now());
+    manifest.save(dir)?;
+
+    for (cat, before, after) in manifest.new_editions(&old) {
+        info!(
+            "CAT{:03}: new edition {} (was {})",
+            cat,
+            after.as_deref().unwrap_or("?"),
+            before.as_deref().unwrap_or("none")
+        );
+    }
+    Ok((manifest, report))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "live")]
+    use rstest::rstest;
+
+    use super::*;
+
+    #[cfg(feature = "live")]
+    #[rstest]
+    #[case("https://example.com/cat062.pdf", true)]
+    #[case("https://example.com/CAT062.PDF?v=2", true)]
+    #[case("https://example.com/publication/cat062", false)]
+    fn test_is_pdf(#[case] url: &str, #[case] res: bool) {
+        assert_eq!(res, is_pdf(url));
+    }
+
+    #[cfg(feature = "live")]
+    #[test]
+    fn test_local_path() {
+        let cat = Category {
+            number: 1,
+            ..Default::default()
+        };
+        let mut taken = HashSet::new();
+        let url = "https://example.com/files/cat001p2_ed1.4.pdf?x=1";
+        assert_eq!("cat001/cat001p2_ed1.4.pdf", local_path(&cat, url, &taken));
+
+        // Same name somewhere else
+        //
+        taken.insert("cat001/cat001p2_ed1.4.pdf".to_owned());
+        let other = "https://example.com/old/cat001p2_ed1.4.pdf";
+        let file = local_path(&cat, other, &taken);
+        assert_eq!(
+            format!(
+                "cat001/cat001p2_ed1.4-{}.pdf",
+                &sha256(other.as_bytes())[..8]
+            ),
+            file
+        );
+        assert_ne!(
+            file,
+            local_path(&cat, "https://example.com/new/cat001p2_ed1.4.pdf", &taken)
+        );
+
+        // No name at all
+        //
+        let file = local_path(&cat, "https://example.com/", &taken);
+        assert_eq!(
+            format!("cat001/{}", &sha256(b"https://example.com/")[..8]),
+            file
+        );
+
+        // Nothing outside of the category
+        //
+        for url in ["https://example.com/a/..", "https://example.com/a/."] {
+            assert_eq!(
+                format!("cat001/{}", &sha256(url.as_bytes())[..8]),
+                local_path(&cat, url, &taken)
+            );
+        }
+    }
+
+    fn entry(category: u8, edition: &str) -> MirrorEntry {
+        MirrorEntry {
+            category,
+            edition: Some(edition.to_owned()),
+            url: format!("https://example.com/cat{:03}.pdf", category),
+            file: format!("cat{:03}/cat{:03}.pdf", category, category),
+            sha256: String::new(),
+            size: 0,
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn test_new_editions() {
+        let old = Manifest {
+            updated_at: None,
+            entries: vec![entry(1, "1.4"), entry(62, "1.18")],
+        };
+        let new = Manifest {
+            updated_at: None,
+            entries: vec![entry(1, "1.4"), entry(21, "2.6"), entry(62, "1.19")],
+        };
+
+        assert_eq!(
+            vec![
+                (21, None, Some("2.6".to_owned())),
+                (62, Some("1.18".to_owned()), Some("1.19".to_owned()))
+            ],
+            new.new_editions(&old)
+        );
+        assert!(new.new_editions(&new).is_empty());
+    }
+
+    #[test]
+    fn test_manifest_load_save() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert_eq!(Manifest::default(), Manifest::load(dir.path()).unwrap());
+        let m = Manifest {
+            updated_at: Some(Utc::now()),
+            entries: vec![entry(1, "1.4")],
+        };
+        m.save(dir.path()).unwrap();
+        assert_eq!(m, Manifest::load(dir.path()).unwrap());
+    }
+
+    /// Serve two PDFs from a local server, honouring `If-None-Match`, for `requests` requests
+    ///
+    #[cfg(feature = "live")]
+    fn serve(requests: usize) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        use tiny_http::{Header, Response, Server};
+
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut seen = vec![];
+            for req in server.incoming_requests().take(requests) {
+                let url = req.url().to_owned();
+                let etag = format!("\"{}\"", url);
+                let cached = req
+                    .headers()
+                    .iter()
+                    .any(|h| h.field.equiv("If-None-Match") && h.value.as_str() == etag);
+                let resp = match url.as_str() {
+                    _ if cached => Response::from_data(vec![]).with_status_code(304),
+                    "/cat001.pdf" | "/cat062.pdf" => Response::from_data(url.as_bytes().to_vec())
+                        .with_header(Header::from_bytes("ETag", etag.as_bytes()).unwrap()),
+                    _ => Response::from_data(vec![]).with_status_code(404),
+                };
+                seen.push(format!("{} {}", url, resp.status_code().0));
+                req.respond(resp).unwrap();
+            }
+            seen
+        });
+        (base, handle)
+    }
+
+    #[cfg(feature = "live")]
+    #[test]
+    fn test_mirror_same_name() {
+        use tiny_http::{Response, Server};
+
+        let dir = tempfile::tempdir().unwrap();
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let handle = std::thread::spawn(move || {
+            for req in server.incoming_requests().take(2) {
+                let body = req.url().as_bytes().to_vec();
+                req.respond(Response::from_data(body)).unwrap();
+            }
+        });
+        let link = |p: &str| Link {
+            title: "Specification".to_owned(),
+            url: format!("{}{}", base, p),
+        };
+        let cats = vec![Category {
+            number: 62,
+            links: vec![link("/a/cat062.pdf"), link("/b/cat062.pdf")],
+            ..Default::default()
+        }];
+
+        let (m, report) = mirror(&cats, dir.path()).unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(2, report.downloaded);
+        assert_ne!(m.entries[0].file, m.entries[1].file);
+        for e in &m.entries {
+            let path = e.url.trim_start_matches(&base);
+            assert_eq!(path.as_bytes(), fs::read(dir.path().join(&e.file)).unwrap());
+        }
+    }
+
+    #[cfg(feature = "live")]
+    #[test]
+    fn test_mirror_truncated() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        // Announce more than what is sent then hang up
+        //
+        let dir = tempfile::tempdir().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n%PDF")
+                .unwrap();
+        });
+        let cats = vec![Category {
+            number: 1,
+            links: vec![Link {
+                title: "Specification".to_owned(),
+                url: format!("{}/cat001.pdf", base),
+            }],
+            ..Default::default()
+        }];
+
+        let (m, report) = mirror(&cats, dir.path()).unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(0, report.downloaded);
+        assert_eq!(1, report.failed.len());
+        assert!(m.entries.is_empty());
+        assert!(!dir.path().join("cat001/cat001.pdf").exists());
+    }
+
+    #[cfg(feature = "live")]
+    #[test]
+    fn test_mirror_write_error() {
+        // A file where the CAT001 directory should be
+        //
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("cat001"), b"").unwrap();
+        let (base, handle) = serve(2);
+        let cats: Vec<_> = [1, 62]
+            .into_iter()
+            .map(|number| Category {
+                number,
+                links: vec![Link {
+                    title: "Specification".to_owned(),
+                    url: format!("{}/cat{:03}.pdf", base, number),
+                }],
+                ..Default::default()
+            })
+            .collect();
+
+        let (m, report) = mirror(&cats, dir.path()).unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(1, report.downloaded);
+        assert_eq!(format!("{}/cat001.pdf", base), report.failed[0].0);
+        assert_eq!(1, m.entries.len());
+        assert_eq!("cat062/cat062.pdf", m.entries[0].file);
+        assert_eq!(m, Manifest::load(dir.path()).unwrap());
+    }
+
+    #[cfg(feature = "live")]
+    #[test]
+    fn test_mirror() {
+        let dir = tempfile::tempdir().unwrap();
+        let (base, handle) = serve(5);
+        let link = |p: &str| Link {
+            title: "Specification".to_owned(),
+            url: format!("{}{}", base, p),
+        };
+        let mut cats = vec![
+            Category {
+                number: 1,
+                edition: Some("1.4".to_owned()),
+                links: vec![link("/cat001.pdf"), link("/publication/cat001")],
+                ..Default::default()
+            },
+            Category {
+                number: 62,
+                edition: Some("1.18".to_owned()),
+                links: vec![link("/cat062.pdf"), link("/missing.pdf")],
+                ..Default::default()
+            },
+        ];
+
+        let (m, report) = mirror(&cats, dir.path()).unwrap();
+
+        assert_eq!(2, report.downloaded);
+        assert_eq!(1, report.failed.len());
+        assert_eq!(2, m.entries.len());
+        assert_eq!("cat001/cat001.pdf", m.entries[0].file);
+        assert_eq!(11, m.entries[0].size);
+        assert_eq!(sha256(b"/cat001.pdf"), m.entries[0].sha256);
+        assert_eq!(
+            b"/cat062.pdf".to_vec(),
+            fs::read(dir.path().join("cat062/cat062.pdf")).unwrap()
+        );
+        assert_eq!(m, Manifest::load(dir.path()).unwrap());
+
+        // Second run, everything is cached and CAT062 has a new edition
+        //
+        cats[1].edition = Some("1.19".to_owned());
+        cats[1].links.pop();
+
+        let (m2, report) = mirror(&cats, dir.path()).unwrap();
+
+        assert_eq!(0, report.downloaded);
+        assert_eq!(2, report.unchanged);
+        assert_eq!(Some("1.19".to_owned()), m2.entries[1].edition);
+        assert_eq!(m.entries[1].sha256, m2.entries[1].sha256);
+        assert_eq!(
+            vec![(62, Some("1.18".to_owned()), Some("1.19".to_owned()))],
+            m2.new_editions(&m)
+        );
+
+        assert_eq!(
+            vec![
+                "/cat001.pdf 200",
+                "/cat062.pdf 200",
+                "/missing.pdf 404",
+                "/cat001.pdf 304",
+                "/cat062.pdf 304"
+            ],
+            handle.join().unwrap()
+        );
+    }
+}
@@ -3,78 +3,252 @@
 //! The main scraper is defined here, calling parts of the module like the parser to prepare data, etc.
 //!
 
+#[cfg(feature = "live")]
+use std::time::Instant;
+
 use anyhow::Result;
+#[cfg(feature = "live")]
+use chrono::Utc;
 use log::{debug, info};
-use regex::Regex;
 use scraper::{Html, Selector};
 
 // Re-export for shorter paths
 //
 pub use area::*;
+pub use asterix::*;
+pub use binary::*;
+pub use bundled::*;
+pub use category::*;
+pub use codegen::*;
 pub use csv_output::*;
+pub use dsi::*;
+pub use envelope::*;
+pub use html_output::*;
+pub use import::*;
+pub use json_output::*;
+pub use markdown_output::*;
+pub use mirror::*;
 pub use parse::*;
+pub use proto::*;
 pub use sac::*;
+pub use schema::*;
+pub use sic::*;
+pub use spreadsheet::*;
+pub use sql_output::*;
+pub use sqlite_output::*;
+pub use table::*;
+pub use tabular::*;
+pub use wireshark::*;
+pub use xml_output::*;
 
 pub mod area;
+pub mod asterix;
+pub mod binary;
+pub mod bundled;
+pub mod category;
+pub mod codegen;
 pub mod csv_output;
+pub mod dsi;
+pub mod envelope;
+pub mod html_output;
+pub mod import;
+pub mod json_output;
+pub mod markdown_output;
+pub mod mirror;
 pub mod parse;
+pub mod proto;
 pub mod sac;
+pub mod schema;
+pub mod sic;
+pub mod spreadsheet;
+pub mod sql_output;
+pub mod sqlite_output;
+pub mod table;
+pub mod tabular;
+pub mod wireshark;
+pub mod xml_output;
+
+/// Official ECTL page listing the SAC codes
+///
+pub const PAGE: &str = "https://www.eurocontrol.int/asterix";
+
+/// How to scrape the page
+///
+#[derive(Clone, Debug, Default)]
+pub struct ScrapeOptions {
+    /// Keep the cell text verbatim instead of going through `normalize_text()`, for auditing
+    pub raw: bool,
+}
+
+impl ScrapeOptions {
+    /// Text of a cell as we want it
+    ///
+    pub fn clean(&self, s: &str) -> String {
+        if self.raw {
+            s.to_owned()
+        } else {
+            normalize_text(s)
+        }
+    }
+}
+
+/// Fetch the official page and scrape it
+///
+#[cfg(feature = "live")]
+pub fn fetch() -> Result<Envelope> {
+    fetch_with(&ScrapeOptions::default())
+}
+
+/// Fetch the official page and scrape it with the given options
+///
+#[cfg(feature = "live")]
+pub fn fetch_with(opts: &ScrapeOptions) -> Result<Envelope> {
+    // Fetch the official page
+    //
+    let now = Instant::now();
+    let doc = reqwest::blocking::get(PAGE)?.text()?;
+    let retrieved = Utc::now();
+    let now = now.elapsed().as_millis();
+
+    info!("Fetch took {} ms", now);
+
+    // Time it
+    //
+    let now = Instant::now();
+    let page = scrape_page(doc, opts)?;
+    let areas: Vec<_> = page.tables.iter().map(Table::to_area).collect();
+    let now = now.elapsed().as_millis();
+
+    info!("Processing took {} ms", now);
+    let mut env = Envelope::new(&areas, retrieved)?;
+    env.page_updated = page.updated;
+    Ok(env)
+}
 
 /// Extract the HTML stuff from the page
 ///
 pub fn scrape_data(doc: String) -> Result<Vec<Area>> {
-    // Define a regex to sanitize some data, don't ask me why some entries have an embedded
-    // <br> or <br />.  Makes no sense to me.
-    //
-    let re = Regex::new(r##"<br>"##).unwrap();
+    scrape_data_with(doc, &ScrapeOptions::default())
+}
+
+/// Extract the HTML stuff from the page with the given options
+///
+pub fn scrape_data_with(doc: String, opts: &ScrapeOptions) -> Result<Vec<Area>> {
+    Ok(scrape_tables(doc, opts)?
+        .iter()
+        .map(Table::to_area)
+        .collect())
+}
+
+/// Everything we get from the page
+///
+#[derive(Clone, Debug, Default)]
+pub struct Page {
+    /// All tables, one per area
+    pub tables: Vec<Table>,
+    /// Last update of the page, as published
+    pub updated: Option<String>,
+}
 
+/// Extract every table of the page with all their columns
+///
+pub fn scrape_tables(doc: String, opts: &ScrapeOptions) -> Result<Vec<Table>> {
+    Ok(scrape_page(doc, opts)?.tables)
+}
+
+/// Extract the tables along with the page metadata
+///
+pub fn scrape_page(doc: String, opts: &ScrapeOptions) -> Result<Page> {
     // Parse the page
     //
     let doc = Html::parse_document(&doc);
 
     // Load the different tabs' header
     //
-    let hdrs = parse_header(&doc)?;
+    let hdrs = parse_header_with(&doc, opts)?;
 
     info!("{} regions found", hdrs.len());
 
-    // We want <table> because sometimes there are 3 <td> and sometimes 2 inside a <tr>.
-    //
     let sel = Selector::parse("table").unwrap();
 
     // Now look into every table header and table in parallel
     //
-    Ok(hdrs
+    let tables = hdrs
         .iter()
         .zip(doc.select(&sel))
         .map(|(name, e)| {
-            // For each line
-            //
             info!("Table({})", name);
-
             debug!("frag={}", e.html());
 
-            // Now we want each <tr>
-            //
-            let sel = Selector::parse("tr").unwrap();
-            let iter = e.select(&sel);
+            Table::from_element(name, e, opts)
+        })
+        .collect();
+
+    Ok(Page {
+        tables,
+        updated: parse_updated(&doc),
+    })
+}
 
-            let mut area = Area::new(name);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            iter.filter(|e| !e.html().contains("SAC")).for_each(|e| {
-                debug!("td={e:?}");
-                let frag = e.html();
+    const PAGE_SAMPLE: &str = include_str!("../../testdata/asterix.html");
 
-                // Filter
-                //
-                let frag = re.replace_all(&frag, "");
+    #[test]
+    fn test_scrape_data() {
+        let areas = scrape_data(PAGE_SAMPLE.to_owned()).unwrap();
 
-                // Get what we want
-                //
-                let (_, (a, b)) = parse_tr(&frag).unwrap();
-                area.add(a, b);
-            });
-            area
-        })
-        .collect::<Vec<_>>())
+        assert_eq!(2, areas.len());
+        assert_eq!("EU Region", areas[0].name());
+        assert_eq!("Asia & Pacific", areas[1].name());
+        assert_eq!("Côte d’Ivoire", areas[0].get("02").unwrap());
+        assert_eq!("Bosnia and Herzegovina", areas[0].get("04").unwrap());
+        assert_eq!("Nested markup here", areas[0].get("06").unwrap());
+        assert_eq!("SAC used for testing", areas[0].get("08").unwrap());
+        assert_eq!(5, areas[0].len());
+        assert_eq!("Vietnam", areas[1].get("94").unwrap());
+    }
+
+    #[test]
+    fn test_scrape_tables() {
+        let tables = scrape_tables(PAGE_SAMPLE.to_owned(), &ScrapeOptions::default()).unwrap();
+
+        assert_eq!(2, tables.len());
+        assert_eq!(3, tables[0].columns.len());
+        assert_eq!(
+            Some("0000 0010"),
+            tables[0].rows[1].get("Binary Representation")
+        );
+        assert_eq!(
+            vec!["SAC(Hexa)", "Country/Geographical Area"],
+            tables[1].columns
+        );
+    }
+
+    #[test]
+    fn test_scrape_page() {
+        let page = scrape_page(PAGE_SAMPLE.to_owned(), &ScrapeOptions::default()).unwrap();
+
+        assert_eq!(Some("12 March 2023".to_owned()), page.updated);
+        assert_eq!(
+            vec![
+                "Codes 00 to 03 are reserved.",
+                "Footnote spanning the table"
+            ],
+            page.tables[0].notes
+        );
+        assert!(page.tables[1].notes.is_empty());
+    }
+
+    #[test]
+    fn test_scrape_data_raw() {
+        let opts = ScrapeOptions { raw: true };
+        let areas = scrape_data_with(PAGE_SAMPLE.to_owned(), &opts).unwrap();
+
+        assert_eq!("Asia &amp; Pacific", areas[1].name());
+        assert!(areas[0].get("04").unwrap().contains("&nbsp;and\n"));
+        assert_eq!("<strong>Vietnam</strong>", areas[1].get("94").unwrap());
+    }
 }
@@ -1,7 +1,7 @@
 //! nom-based parser for the data we want to extract.
 //!
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use log::debug;
 use nom::{
     branch::alt,
@@ -10,7 +10,111 @@ use nom::{
     sequence::{delimited, terminated, tuple},
     IResult,
 };
-use scraper::{Html, Selector};
+use regex::Regex;
+use scraper::{ElementRef, Html, Node, Selector};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::core::ScrapeOptions;
+
+/// Clean up the text of a cell: normalize to NFC, collapse whitespace (including non-breaking
+/// spaces) and trim.  Text from the DOM has its entities decoded already so a literal `&lt;`
+/// stays as-is, see [`decode_text`] for HTML source.
+///
+pub fn normalize_text(input: &str) -> String {
+    input
+        .nfc()
+        .collect::<String>()
+        .split(char::is_whitespace)
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Clean up HTML source as read by the nom fallback: decode HTML entities then
+/// [`normalize_text`]
+///
+pub fn decode_text(input: &str) -> String {
+    normalize_text(&html_escape::decode_html_entities(input))
+}
+
+/// Get all the text inside an element whatever the markup (`<a>`, `<em>`, `<p>`, …), `<br>`
+/// and block elements are turned into a space
+///
+pub fn cell_text(cell: ElementRef) -> String {
+    let mut text = String::new();
+    for node in cell.descendants() {
+        match node.value() {
+            Node::Text(t) => text.push_str(t),
+            Node::Element(e) if matches!(e.name(), "br" | "p" | "div" | "li") => text.push(' '),
+            _ => (),
+        }
+    }
+    text
+}
+
+/// Text of all the `<td>` and `<th>` cells of a row, from the DOM
+///
+pub fn row_cells(row: ElementRef) -> Vec<String> {
+    let sel = Selector::parse("td, th").unwrap();
+    row.select(&sel).map(cell_text).collect()
+}
+
+/// HTML inside all the `<td>` and `<th>` cells of a row, as published, for the raw mode
+///
+pub fn row_html(row: ElementRef) -> Vec<String> {
+    let sel = Selector::parse("td, th").unwrap();
+    row.select(&sel).map(|c| c.inner_html()).collect()
+}
+
+/// Find when the page was last updated, either from the metadata or from text like
+/// "Last updated: 12 March 2023", the date is returned as published
+///
+pub fn parse_updated(doc: &Html) -> Option<String> {
+    let sel = Selector::parse(
+        r#"meta[property="article:modified_time"], meta[name="last-modified"], meta[name="dcterms.modified"]"#,
+    )
+    .unwrap();
+    if let Some(date) = doc.select(&sel).find_map(|e| e.value().attr("content")) {
+        debug!("updated={} from meta", date);
+        return Some(normalize_text(date));
+    }
+
+    let re = Regex::new(
+        r"(?i)(?:last\s+updated?|last\s+modified|updated\s+on|revision\s+date)\s*(?:on)?\s*:?\s*(\d{4}-\d{2}-\d{2}|\d{1,2}/\d{1,2}/\d{4}|\d{1,2}(?:st|nd|rd|th)?\s+[[:alpha:]]+\s+\d{4}|[[:alpha:]]+\s+\d{1,2},?\s+\d{4})",
+    )
+    .unwrap();
+    let text = normalize_text(&doc.root_element().text().collect::<Vec<_>>().join(" "));
+    let date = re.captures(&text).map(|c| c[1].to_owned());
+    debug!("updated={:?} from text", date);
+    date
+}
+
+/// A header row is in `<thead>`, only has `<th>` cells or has `<td>` cells whose text is all
+/// in `<strong>` or `<b>`, like some tabs of the page do
+///
+pub fn is_header_row(row: ElementRef) -> bool {
+    let th = Selector::parse("th").unwrap();
+    let td = Selector::parse("td").unwrap();
+    let bold = Selector::parse("strong, b").unwrap();
+
+    let in_thead = row
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .any(|e| e.value().name() == "thead");
+    if in_thead || (row.select(&th).next().is_some() && row.select(&td).next().is_none()) {
+        return true;
+    }
+
+    // Every cell with text must be in bold, with at least two of them
+    //
+    let text = |e: ElementRef| normalize_text(&e.text().collect::<String>());
+    let cells: Vec<_> = row.select(&td).filter(|c| !text(*c).is_empty()).collect();
+    cells.len() >= 2
+        && cells.iter().all(|c| {
+            let strong: String = c.select(&bold).map(text).collect::<Vec<_>>().join(" ");
+            normalize_text(&strong) == text(*c)
+        })
+}
 
 fn parse_content(input: &str) -> IResult<&str, &str> {
     alt((parse_strong, take_until("<")))(input)
@@ -54,19 +158,39 @@ pub fn parse_tr(input: &str) -> IResult<&str, (&str, &str)> {
     )(input)
 }
 
+/// Names of the tabs, one per table
+///
 pub fn parse_header(input: &Html) -> Result<Vec<String>> {
+    parse_header_with(input, &ScrapeOptions::default())
+}
+
+/// Names of the tabs from the DOM whatever the markup inside, the HTML as published in raw mode
+///
+/// The nom grammar is only tried when the DOM has no text.
+///
+pub fn parse_header_with(input: &Html, opts: &ScrapeOptions) -> Result<Vec<String>> {
     let sel = Selector::parse("a > span, [class=field--type-advanced-title]").unwrap();
-    let doc = input.select(&sel);
-    let r = doc
+    input
+        .select(&sel)
         .filter(|e| !e.html().contains("class"))
         .map(|e| {
+            let r = match opts.raw {
+                true => e.inner_html(),
+                false => cell_text(e),
+            };
+            if !r.trim().is_empty() {
+                debug!("{}", r);
+                return Ok(r);
+            }
             let frag = e.html();
-            let (_, r) = parse_span(&frag).unwrap();
-            debug!("{}", r);
-            r.to_owned()
+            let (_, r) =
+                parse_span(&frag).map_err(|err| anyhow!("bad tab header {}: {}", frag, err))?;
+            match opts.raw {
+                true => Ok(r.to_owned()),
+                false => Ok(decode_text(r)),
+            }
         })
-        .collect::<Vec<_>>();
-    Ok(r)
+        .collect()
 }
 
 #[cfg(test)]
@@ -86,6 +210,60 @@ mod tests {
         assert_eq!(res, r)
     }
 
+    #[rstest]
+    #[case("foo", "foo")]
+    #[case("  foo\n  bar\t", "foo bar")]
+    #[case("foo\u{a0}bar", "foo bar")]
+    #[case("Vietnam &amp; co", "Vietnam &amp; co")]
+    #[case("Cote\u{0302}", "Cotê")]
+    fn test_normalize_text(#[case] input: &str, #[case] res: &str) {
+        assert_eq!(res, normalize_text(input));
+    }
+
+    #[rstest]
+    #[case("Vietnam &amp; co", "Vietnam & co")]
+    #[case("foo&nbsp;bar", "foo bar")]
+    #[case("C&ocirc;te d&#8217;Ivoire", "Côte d’Ivoire")]
+    #[case("&amp;lt;", "&lt;")]
+    fn test_decode_text(#[case] input: &str, #[case] res: &str) {
+        assert_eq!(res, decode_text(input));
+    }
+
+    #[rstest]
+    #[case(
+        r#"<meta property="article:modified_time" content="2023-03-12T10:00:00+01:00">"#,
+        Some("2023-03-12T10:00:00+01:00")
+    )]
+    #[case("<p>Last updated: 12 March 2023</p>", Some("12 March 2023"))]
+    #[case("<p>Page last modified on 3rd May 2022.</p>", Some("3rd May 2022"))]
+    #[case("<p>Updated on 2021-01-31</p>", Some("2021-01-31"))]
+    #[case("<p>Last update: March 12, 2023</p>", Some("March 12, 2023"))]
+    #[case("<p>Nothing here</p>", None)]
+    fn test_parse_updated(#[case] input: &str, #[case] res: Option<&str>) {
+        let doc = Html::parse_document(&format!("<html><head></head><body>{input}</body></html>"));
+        assert_eq!(res.map(|s| s.to_owned()), parse_updated(&doc));
+    }
+
+    fn first_row(html: &str) -> Vec<String> {
+        let doc = Html::parse_fragment(&format!("<table>{html}</table>"));
+        let sel = Selector::parse("tr").unwrap();
+        let row = doc.select(&sel).next().unwrap();
+        row_cells(row)
+    }
+
+    #[rstest]
+    #[case("<tr><td>94</td><td>Vietnam</td></tr>", vec!["94", "Vietnam"])]
+    #[case("<tr><th>SAC</th><th><strong>Area</strong></th></tr>", vec!["SAC", "Area"])]
+    #[case(
+        r#"<tr><td class="c" align="left">94</td><td><a href="/vn"><em>Viet</em>nam</a></td></tr>"#,
+        vec!["94", "Vietnam"]
+    )]
+    #[case("<tr><td>94</td><td>North<br />South</td></tr>", vec!["94", "North South"])]
+    #[case("<tr><td><p>94</p></td><td><p>Vietnam</p></td></tr>", vec![" 94", " Vietnam"])]
+    fn test_row_cells(#[case] input: &str, #[case] res: Vec<&str>) {
+        assert_eq!(res, first_row(input));
+    }
+
     #[rstest]
     #[case("<span>foo</span>", "foo")]
     #[case("<span>EU Region</span>", "EU Region")]
@@ -95,6 +273,62 @@ mod tests {
         assert_eq!(res, r)
     }
 
+    fn headers(html: &str, raw: bool) -> Vec<String> {
+        let doc = Html::parse_document(&format!("<html><body><ul>{html}</ul></body></html>"));
+        parse_header_with(&doc, &ScrapeOptions { raw }).unwrap()
+    }
+
+    #[rstest]
+    #[case("<li><a><span>EU Region</span></a></li>", "EU Region")]
+    #[case("<li><a><span><em>EU</em> Region</span></a></li>", "EU Region")]
+    #[case(
+        "<li><a><span><span><em>Asia</em> &amp; Pacific</span></span></a></li>",
+        "Asia & Pacific"
+    )]
+    fn test_parse_header(#[case] input: &str, #[case] res: &str) {
+        assert_eq!(vec![res], headers(input, false));
+    }
+
+    #[test]
+    fn test_parse_header_raw() {
+        assert_eq!(
+            vec!["<em>Asia</em> &amp; Pacific"],
+            headers(
+                "<li><a><span><em>Asia</em> &amp; Pacific</span></a></li>",
+                true
+            )
+        );
+    }
+
+    #[rstest]
+    #[case("<table><tr><th>SAC</th><th>Country</th></tr></table>", true)]
+    #[case(
+        "<table><thead><tr><td>SAC</td><td>Country</td></tr></thead></table>",
+        true
+    )]
+    #[case(
+        "<table><tr><td><strong>SAC</strong></td><td><b>Country</b></td></tr></table>",
+        true
+    )]
+    #[case(
+        "<table><tr><td><strong>SAC</strong></td><td></td></tr></table>",
+        false
+    )]
+    #[case(
+        "<table><tr><td>94</td><td><strong>Vietnam</strong></td></tr></table>",
+        false
+    )]
+    #[case(
+        "<table><tr><td><strong>9</strong>4</td><td><strong>Vietnam</strong></td></tr></table>",
+        false
+    )]
+    #[case("<table><tr><td>94</td><td>Vietnam</td></tr></table>", false)]
+    fn test_is_header_row(#[case] html: &str, #[case] res: bool) {
+        let doc = Html::parse_fragment(html);
+        let sel = Selector::parse("tr").unwrap();
+        assert_eq!(res, is_header_row(doc.select(&sel).next().unwrap()));
+    }
+
     #[test]
     fn test_parse_two() {
         let input = "<td>foo</td><td>bar</td>";
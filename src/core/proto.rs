@@ -0,0 +1,297 @@
+//! Module dealing with Protobuf output and input
+//!
+//! The definition is in `proto/fetch_sac.proto`, the Rust types in [`pb`] are generated from
+//! it at build time.
+//!
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use log::trace;
+use prost::Message;
+
+use crate::core::{content_hash, Area, Categories, Category, Envelope, Link, PAGE, SAC};
+
+/// Types generated from `proto/fetch_sac.proto`
+///
+pub mod pb {
+    include!(concat!(env!("OUT_DIR"), "/fetch_sac.rs"));
+}
+
+/// The `.proto` definition itself
+///
+pub const PROTO: &str = include_str!("../../proto/fetch_sac.proto");
+
+/// Protobuf timestamp from our dates
+///
+fn timestamp(date: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: date.timestamp(),
+        nanos: date.timestamp_subsec_nanos() as i32,
+    }
+}
+
+/// Our dates from a protobuf timestamp, which is mandatory
+///
+fn retrieved_at(ts: Option<prost_types::Timestamp>) -> Result<DateTime<Utc>> {
+    match ts {
+        Some(ts) => Utc
+            .timestamp_opt(ts.seconds, ts.nanos as u32)
+            .single()
+            .ok_or_else(|| anyhow!("invalid timestamp {:?}", ts)),
+        None => Err(anyhow!("no retrieval date in registry")),
+    }
+}
+
+/// Build the `Registry` message for `areas`, `updated` being the last update of the page
+///
+pub fn to_registry(
+    areas: &[Area],
+    retrieved: DateTime<Utc>,
+    updated: Option<&str>,
+) -> Result<pb::Registry> {
+    let hash = content_hash(areas)?;
+    let areas: Vec<_> = areas
+        .iter()
+        .map(|area| pb::Area {
+            name: area.name(),
+            sacs: area
+                .iter()
+                .map(|(code, label)| {
+                    let sac = SAC::try_from(code.as_str()).unwrap_or_default();
+                    let kind = match &sac {
+                        SAC::Hex(s) => u8::from_str_radix(s, 16)
+                            .ok()
+                            .map(|v| pb::sac::Kind::Value(v.into())),
+                        SAC::Range { lo, hi } => Some(pb::sac::Kind::Range(pb::Range {
+                            lo: *lo as u32,
+                            hi: *hi as u32,
+                        })),
+                        SAC::Empty => None,
+                    };
+                    pb::Sac {
+                        code: code.to_owned(),
+                        label: label.to_owned(),
+                        kind,
+                        binary: sac.binary().unwrap_or_default(),
+                        row: area.row(code).unwrap_or_default() as u32,
+                    }
+                })
+                .collect(),
+            notes: area.notes().to_vec(),
+        })
+        .collect();
+
+    Ok(pb::Registry {
+        source_url: PAGE.to_owned(),
+        retrieved_at: Some(timestamp(retrieved)),
+        hash,
+        areas,
+        page_updated: updated.map(|s| s.to_owned()),
+    })
+}
+
+/// Get our areas back from the protobuf ones, in the same order and with the page order from
+/// `row` (files without it keep their own order)
+///
+fn areas_from(areas: &[pb::Area]) -> Vec<Area> {
+    areas
+        .iter()
+        .map(|a| {
+            let mut area = Area::new(&a.name);
+            a.sacs.iter().for_each(|s| {
+                area.add(&s.code, &s.label);
+            });
+            a.notes.iter().for_each(|n| {
+                area.add_note(n);
+            });
+
+            let mut sacs: Vec<_> = a.sacs.iter().enumerate().collect();
+            sacs.sort_by_key(|(i, s)| match s.row {
+                0 => (1, *i),
+                row => (0, row as usize),
+            });
+            let rows: Vec<_> = sacs.into_iter().map(|(_, s)| s.code.clone()).collect();
+            area.set_rows(&rows);
+            area
+        })
+        .collect()
+}
+
+/// Serialize a `Registry` message
+///
+pub fn to_protobuf(
+    areas: &[Area],
+    retrieved: DateTime<Utc>,
+    updated: Option<&str>,
+) -> Result<Vec<u8>> {
+    trace!("Generating protobuf output…");
+    Ok(to_registry(areas, retrieved, updated)?.encode_to_vec())
+}
+
+/// Read back a `Registry` message from [`to_protobuf`]
+///
+pub fn from_protobuf(data: &[u8]) -> Result<Envelope> {
+    let reg = pb::Registry::decode(data)?;
+    let retrieved = retrieved_at(reg.retrieved_at)?;
+    let mut env = Envelope::new(&areas_from(&reg.areas), retrieved)?;
+    env.source_url = reg.source_url;
+    env.page_updated = reg.page_updated;
+    Ok(env)
+}
+
+/// Serialize a `CategoryList` message
+///
+pub fn categories_to_protobuf(cats: &Categories) -> Result<Vec<u8>> {
+    trace!("Generating categories protobuf output…");
+    let list = pb::CategoryList {
+        source_url: cats.source_url.to_owned(),
+        retrieved_at: Some(timestamp(cats.retrieved_at)),
+        hash: cats.hash.to_owned(),
+        categories: cats
+            .categories
+            .iter()
+            .map(|c| pb::Category {
+                number: c.number.into(),
+                title: c.title.to_owned(),
+                edition: c.edition.clone(),
+                date: c.date.clone(),
+                links: c
+                    .links
+                    .iter()
+                    .map(|l| pb::Link {
+                        title: l.title.to_owned(),
+                        url: l.url.to_owned(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+        page_updated: cats.page_updated.clone(),
+    };
+    Ok(list.encode_to_vec())
+}
+
+/// Read back a `CategoryList` message from [`categories_to_protobuf`]
+///
+pub fn categories_from_protobuf(data: &[u8]) -> Result<Categories> {
+    let list = pb::CategoryList::decode(data)?;
+    let retrieved = retrieved_at(list.retrieved_at)?;
+    let cats = list
+        .categories
+        .into_iter()
+        .map(|c| {
+            Ok(Category {
+                number: u8::try_from(c.number)
+                    .map_err(|_| anyhow!("invalid category {}", c.number))?,
+                title: c.title,
+                edition: c.edition,
+                date: c.date,
+                links: c
+                    .links
+                    .into_iter()
+                    .map(|l| Link {
+                        title: l.title,
+                        url: l.url,
+                    })
+                    .collect(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut res = Categories::new(&cats, retrieved)?;
+    res.source_url = list.source_url;
+    res.page_updated = list.page_updated;
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Order;
+
+    fn sample() -> Vec<Area> {
+        let mut a = Area::new("Asia");
+        a.add("94", "Vietnam");
+        a.add("A0...C3", "Reserved");
+        vec![a]
+    }
+
+    #[test]
+    fn test_to_registry() {
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let reg = to_registry(&sample(), date, None).unwrap();
+
+        assert_eq!(PAGE, reg.source_url);
+        assert_eq!(1680350400, reg.retrieved_at.unwrap().seconds);
+        let sacs = &reg.areas[0].sacs;
+        assert_eq!(Some(pb::sac::Kind::Value(0x94)), sacs[0].kind);
+        assert_eq!("10010100", sacs[0].binary);
+        assert_eq!(
+            Some(pb::sac::Kind::Range(pb::Range { lo: 0xA0, hi: 0xC3 })),
+            sacs[1].kind
+        );
+    }
+
+    #[test]
+    fn test_protobuf_roundtrip() {
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let data = to_protobuf(&sample(), date, None).unwrap();
+
+        let env = from_protobuf(&data).unwrap();
+        assert_eq!(sample(), env.areas);
+        assert_eq!(date, env.retrieved_at);
+        assert_eq!(content_hash(&sample()).unwrap(), env.hash);
+        assert_eq!(None, env.page_updated);
+    }
+
+    #[test]
+    fn test_protobuf_roundtrip_page() {
+        let mut a = Area::new("Asia");
+        a.add("A0...C3", "Reserved");
+        a.add("94", "Vietnam");
+        a.add("12", "Laos");
+        a.add_note("Codes in italics are reserved.");
+        a.sort(Order::Hex);
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let data = to_protobuf(&[a.clone()], date, Some("12 March 2023")).unwrap();
+
+        let env = from_protobuf(&data).unwrap();
+        assert_eq!(Some("12 March 2023".to_owned()), env.page_updated);
+        assert_eq!(vec![a.clone()], env.areas);
+
+        let mut b = env.areas[0].clone();
+        assert_eq!(a.notes(), b.notes());
+        assert_eq!(Some(1), b.row("A0...C3"));
+        assert_eq!(vec!["12", "94", "A0...C3"], b.keys().collect::<Vec<_>>());
+        b.sort(Order::Page);
+        assert_eq!(vec!["A0...C3", "94", "12"], b.keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_protobuf_garbage() {
+        assert!(from_protobuf(b"garbage").is_err());
+    }
+
+    #[test]
+    fn test_categories_protobuf_roundtrip() {
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let cat = Category {
+            number: 62,
+            title: "SDPS Track Messages".to_owned(),
+            edition: Some("1.19".to_owned()),
+            date: None,
+            links: vec![Link {
+                title: "Specification".to_owned(),
+                url: "https://www.eurocontrol.int/cat062.pdf".to_owned(),
+            }],
+        };
+        let mut cats = Categories::new(&[cat], date).unwrap();
+        cats.page_updated = Some("12 March 2023".to_owned());
+
+        let back = categories_from_protobuf(&categories_to_protobuf(&cats).unwrap()).unwrap();
+        assert_eq!(cats.categories, back.categories);
+        assert_eq!(cats.hash, back.hash);
+        assert_eq!(cats.retrieved_at, back.retrieved_at);
+        assert_eq!(cats.page_updated, back.page_updated);
+
+        assert!(categories_from_protobuf(b"garbage").is_err());
+    }
+}
@@ -6,15 +6,18 @@
 
 use std::fmt::{Display, Formatter};
 
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 // ----------------------------------
 
-#[allow(clippy::upper_case_acronyms)]
-
 /// Either  regular hex string or a range
 ///
-#[derive(Clone, Debug, PartialOrd, Ord, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(
+    Clone, Debug, PartialOrd, Ord, Serialize, Deserialize, Eq, PartialEq, Hash, JsonSchema,
+)]
 pub enum SAC {
     /// Simple hex value
     Hex(String),
@@ -30,6 +33,16 @@ impl SAC {
     pub fn new() -> Self {
         SAC::Empty
     }
+
+    /// Binary representation as published by ECTL, ranges are shown as `lo...hi`
+    ///
+    pub fn binary(&self) -> Option<String> {
+        match self {
+            SAC::Empty => None,
+            SAC::Hex(s) => u8::from_str_radix(s, 16).ok().map(|v| format!("{:08b}", v)),
+            SAC::Range { lo, hi } => Some(format!("{:08b}...{:08b}", lo, hi)),
+        }
+    }
 }
 
 impl Default for SAC {
@@ -54,17 +67,28 @@ impl Display for SAC {
     }
 }
 
-impl From<&str> for SAC {
-    /// Easier to have than direct ::from()
+impl TryFrom<&str> for SAC {
+    type Error = anyhow::Error;
+
+    /// Parse a code as published: a hex byte like "94" or a range like "A0...C3", empty is
+    /// `SAC::Empty`
     ///
-    fn from(value: &str) -> Self {
-        if value.contains("...") {
-            let val: Vec<&str> = value.split("...").collect();
-            let lo = usize::from_str_radix(val[0], 16).unwrap();
-            let hi = usize::from_str_radix(val[1], 16).unwrap();
-            SAC::Range { lo, hi }
-        } else {
-            SAC::Hex(value.to_owned())
+    fn try_from(value: &str) -> Result<Self> {
+        let hex =
+            |s: &str| u8::from_str_radix(s, 16).map_err(|_| anyhow!("invalid SAC {:?}", value));
+        match value.split_once("...") {
+            Some((lo, hi)) => {
+                let (lo, hi) = (hex(lo)?, hex(hi)?);
+                if lo > hi {
+                    return Err(anyhow!("invalid SAC range {:?}", value));
+                }
+                Ok(SAC::Range {
+                    lo: lo.into(),
+                    hi: hi.into(),
+                })
+            }
+            None if value.is_empty() => Ok(SAC::Empty),
+            None => hex(value).map(|_| SAC::Hex(value.to_owned())),
         }
     }
 }
@@ -99,8 +123,20 @@ mod tests {
     #[case("A4", SAC::Hex("A4".to_owned()))]
     #[case("00", SAC::Hex("00".to_owned()))]
     #[case("A0...C3", SAC::Range {lo: 160, hi: 195})]
+    #[case("", SAC::Empty)]
     fn test_sac_from_str(#[case] num: &str, #[case] sac: SAC) {
-        assert_eq!(sac, SAC::from(num))
+        assert_eq!(sac, SAC::try_from(num).unwrap())
+    }
+
+    #[rstest]
+    #[case("ZZ")]
+    #[case("X...Y")]
+    #[case("A0...")]
+    #[case("C3...A0")]
+    #[case("100")]
+    #[case("A0...1C3")]
+    fn test_sac_from_str_bad(#[case] num: &str) {
+        assert!(SAC::try_from(num).is_err())
     }
 
     #[rstest]
@@ -109,4 +145,21 @@ mod tests {
     fn test_sac_from_usize(#[case] num: usize, #[case] sac: SAC) {
         assert_eq!(sac, SAC::from(num))
     }
+
+    #[rstest]
+    #[case("94", Some("10010100"))]
+    #[case("00", Some("00000000"))]
+    #[case("A0...C3", Some("10100000...11000011"))]
+    #[case("", None)]
+    fn test_sac_binary(#[case] num: &str, #[case] res: Option<&str>) {
+        assert_eq!(
+            res.map(|s| s.to_owned()),
+            SAC::try_from(num).unwrap().binary()
+        )
+    }
+
+    #[test]
+    fn test_sac_binary_bad_hex() {
+        assert_eq!(None, SAC::Hex("ZZ".to_owned()).binary())
+    }
 }
@@ -0,0 +1,155 @@
+//! Module generating JSON Schema for all our structured outputs
+//!
+//! Schemas are derived from the Rust types so they follow the serde derives, copies are
+//! published in `schemas/` and a test checks they are up-to-date.
+//!
+
+use anyhow::Result;
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+
+use crate::core::{Area, Categories, Envelope, Record, Sites, SAC};
+
+/// Every structured output we have a schema for
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SchemaKind {
+    /// List of areas, aka `-F json`
+    Areas,
+    /// One area
+    Area,
+    /// One SAC code
+    Sac,
+    /// One record of `-F ndjson`
+    Record,
+    /// `-F json-envelope`
+    Envelope,
+    /// `-F json-envelope categories`
+    Categories,
+    /// `-F json-envelope --sic`
+    Sites,
+}
+
+impl SchemaKind {
+    /// All of them
+    ///
+    pub const ALL: [SchemaKind; 7] = [
+        SchemaKind::Areas,
+        SchemaKind::Area,
+        SchemaKind::Sac,
+        SchemaKind::Record,
+        SchemaKind::Envelope,
+        SchemaKind::Categories,
+        SchemaKind::Sites,
+    ];
+
+    /// Generate the schema
+    ///
+    pub fn generate(&self) -> RootSchema {
+        match self {
+            SchemaKind::Areas => schema_for!(Vec<Area>),
+            SchemaKind::Area => schema_for!(Area),
+            SchemaKind::Sac => schema_for!(SAC),
+            SchemaKind::Record => schema_for!(Record),
+            SchemaKind::Envelope => schema_for!(Envelope),
+            SchemaKind::Categories => schema_for!(Categories),
+            SchemaKind::Sites => schema_for!(Sites),
+        }
+    }
+
+    /// Generate the schema as pretty-printed JSON
+    ///
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.generate())?)
+    }
+
+    /// Name of the published file in `schemas/`
+    ///
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            SchemaKind::Areas => "areas.schema.json",
+            SchemaKind::Area => "area.schema.json",
+            SchemaKind::Sac => "sac.schema.json",
+            SchemaKind::Record => "record.schema.json",
+            SchemaKind::Envelope => "envelope.schema.json",
+            SchemaKind::Categories => "categories.schema.json",
+            SchemaKind::Sites => "sites.schema.json",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use chrono::Utc;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::core::{to_records, Category, DataSourceId, MergedSite};
+
+    fn sample() -> Vec<Area> {
+        let mut a = Area::new("Asia");
+        a.add("94", "Vietnam");
+        a.add("A0...C3", "Reserved");
+        vec![a]
+    }
+
+    fn check<T: serde::Serialize>(kind: SchemaKind, data: &T) {
+        let schema = serde_json::to_value(kind.generate()).unwrap();
+        let data = serde_json::to_value(data).unwrap();
+        let res = jsonschema::validate(&schema, &data);
+        assert!(res.is_ok(), "{:?}: {:?}", kind, res);
+    }
+
+    #[test]
+    fn test_schemas_validate_output() {
+        let areas = sample();
+
+        check(SchemaKind::Areas, &areas);
+        check(SchemaKind::Area, &areas[0]);
+        check(SchemaKind::Sac, &SAC::try_from("94").unwrap());
+        check(SchemaKind::Sac, &SAC::try_from("A0...C3").unwrap());
+        check(SchemaKind::Record, &to_records(&areas)[0]);
+        check(
+            SchemaKind::Envelope,
+            &Envelope::new(&areas, Utc::now()).unwrap(),
+        );
+        let cats = [Category {
+            number: 62,
+            title: "SDPS Track Messages".to_owned(),
+            ..Default::default()
+        }];
+        check(
+            SchemaKind::Categories,
+            &Categories::new(&cats, Utc::now()).unwrap(),
+        );
+        let sites = [MergedSite {
+            id: DataSourceId::new(0x94, 1),
+            area: Some("Asia".to_owned()),
+            label: Some("Vietnam".to_owned()),
+            name: "Noi Bai".to_owned(),
+            sensor: None,
+            owner: None,
+        }];
+        check(SchemaKind::Sites, &Sites::new(&sites, Utc::now()).unwrap());
+    }
+
+    #[test]
+    fn test_schemas_are_published() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("schemas");
+
+        for kind in SchemaKind::ALL {
+            let published = fs::read_to_string(dir.join(kind.file_name())).unwrap();
+            let published: Value = serde_json::from_str(&published).unwrap();
+            let current = serde_json::to_value(kind.generate()).unwrap();
+            assert_eq!(
+                current,
+                published,
+                "schemas/{} is outdated, regenerate it with `fetch-sac schema <kind>`",
+                kind.file_name()
+            );
+        }
+    }
+}
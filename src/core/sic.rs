@@ -0,0 +1,412 @@
+//! Module dealing with a local SIC registry
+//!
+//! ECTL publishes the SACs but every ANSP assigns the SICs inside its own SAC.  The registry
+//! is a local file mapping SAC and SIC to a site, in TOML, YAML or CSV:
+//!
+//! ```toml
+//! [[site]]
+//! sac = "94"
+//! sic = 1
+//! name = "Noi Bai"
+//! sensor = "PSR/SSR"
+//! owner = "VATM"
+//! ```
+//!
+//! The SAC is written in hex like the official list, the SIC as a plain number.  Everything
+//! printed afterwards, messages and tables, shows both in hex like [`DataSourceId`], with the
+//! decimal pair in its own column.  The registry is checked against the official list and
+//! merged with it, [`Sites`] wraps the result for `-F json-envelope`.
+//!
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use csv::{ReaderBuilder, Trim};
+use log::{debug, info};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::core::{lookup_sac, Area, DataSourceId, Tabular, PAGE, SCHEMA_VERSION};
+
+/// One site of the registry, as written in the file
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Site {
+    /// SAC in hex
+    #[serde(alias = "SAC")]
+    pub sac: String,
+    /// SIC in decimal
+    #[serde(alias = "SIC")]
+    pub sic: u8,
+    /// Name of the site
+    #[serde(alias = "Name")]
+    pub name: String,
+    /// Type of sensor, e.g. "PSR/SSR", "Mode S", "ADS-B", "WAM"
+    #[serde(default, alias = "Sensor")]
+    pub sensor: Option<String>,
+    /// Who operates it
+    #[serde(default, alias = "Owner")]
+    pub owner: Option<String>,
+}
+
+impl Site {
+    /// SAC/SIC pair, fails if the SAC is not a hex byte
+    ///
+    pub fn id(&self) -> Result<DataSourceId> {
+        let sac = u8::from_str_radix(self.sac.trim(), 16)
+            .map_err(|_| anyhow!("invalid SAC {} for {}", self.sac, self.name))?;
+        Ok(DataSourceId::new(sac, self.sic))
+    }
+}
+
+/// The whole registry
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SicRegistry {
+    /// All sites, `[[site]]` in TOML
+    #[serde(rename = "site", alias = "sites", default)]
+    pub sites: Vec<Site>,
+}
+
+/// Something wrong in the registry
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RegistryIssue {
+    /// SAC which is not a hex byte
+    BadSac { sac: String, name: String },
+    /// SAC not in the official list
+    UnknownSac { id: DataSourceId, name: String },
+    /// Same SAC/SIC for several sites
+    Duplicate {
+        id: DataSourceId,
+        names: Vec<String>,
+    },
+}
+
+impl Display for RegistryIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryIssue::BadSac { sac, name } => write!(f, "{}: invalid SAC {}", name, sac),
+            RegistryIssue::UnknownSac { id, name } => {
+                write!(f, "{}: {} has an unknown SAC", name, id)
+            }
+            RegistryIssue::Duplicate { id, names } => {
+                write!(f, "{} used by {}", id, names.join(", "))
+            }
+        }
+    }
+}
+
+/// One site with what the official list says about its SAC
+///
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MergedSite {
+    /// SAC/SIC pair
+    pub id: DataSourceId,
+    /// Area of the SAC, if known
+    pub area: Option<String>,
+    /// Label of the SAC (country, etc.), if known
+    pub label: Option<String>,
+    /// Name of the site
+    pub name: String,
+    /// Type of sensor
+    pub sensor: Option<String>,
+    /// Who operates it
+    pub owner: Option<String>,
+}
+
+impl Display for MergedSite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {}", self.id, self.name)?;
+        let details: Vec<_> = [&self.sensor, &self.owner]
+            .into_iter()
+            .flatten()
+            .map(|s| s.as_str())
+            .collect();
+        if !details.is_empty() {
+            write!(f, " ({})", details.join(", "))?;
+        }
+        match (&self.area, &self.label) {
+            (Some(area), Some(label)) => write!(f, " in {} / {}", area, label),
+            _ => write!(f, " in unknown SAC"),
+        }
+    }
+}
+
+impl Tabular for MergedSite {
+    const HEADER: &'static [&'static str] = &[
+        "Region", "SAC", "SIC", "Label", "Name", "Sensor", "Owner", "Decimal",
+    ];
+    const NAME: &'static str = "Sites";
+    const ELEMENT: &'static str = "site";
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.area.clone().unwrap_or_default(),
+            format!("{:02X}", self.id.sac),
+            format!("{:02X}", self.id.sic),
+            self.label.clone().unwrap_or_default(),
+            self.name.to_owned(),
+            self.sensor.clone().unwrap_or_default(),
+            self.owner.clone().unwrap_or_default(),
+            self.id.decimal(),
+        ]
+    }
+}
+
+/// Merged sites with where and when the SAC list was retrieved, like `Envelope` for areas
+///
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Sites {
+    /// Version of this format
+    pub schema_version: u32,
+    /// Where the SAC list comes from
+    pub source_url: String,
+    /// When the SAC list was retrieved
+    pub retrieved_at: DateTime<Utc>,
+    /// Last update of the page itself, as published
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_updated: Option<String>,
+    /// `sha256:` followed by the hash of the compact JSON of `sites`
+    pub hash: String,
+    /// The data itself, sorted by SAC/SIC
+    pub sites: Vec<MergedSite>,
+}
+
+impl Sites {
+    /// Wrap `sites` merged with the SAC list retrieved at `retrieved`
+    ///
+    pub fn new(sites: &[MergedSite], retrieved: DateTime<Utc>) -> Result<Self> {
+        let data = serde_json::to_vec(sites)?;
+        Ok(Sites {
+            schema_version: SCHEMA_VERSION,
+            source_url: PAGE.to_owned(),
+            retrieved_at: retrieved,
+            page_updated: None,
+            hash: format!("sha256:{:x}", Sha256::digest(data)),
+            sites: sites.to_vec(),
+        })
+    }
+}
+
+impl SicRegistry {
+    /// Load the registry, the format comes from the extension (`.toml`, `.yaml`/`.yml`, `.csv`)
+    ///
+    pub fn load(path: &Path) -> Result<Self> {
+        info!("Reading {}...", path.to_string_lossy());
+        let data = fs::read_to_string(path)?;
+
+        let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        debug!("ext={}", ext);
+        match ext {
+            "toml" => Ok(toml::from_str(&data)?),
+            "yaml" | "yml" => Ok(serde_yaml::from_str(&data)?),
+            "csv" => Self::from_csv(&data),
+            _ => Err(anyhow!(
+                "unknown registry format for {}",
+                path.to_string_lossy()
+            )),
+        }
+    }
+
+    /// Read CSV with a `sac,sic,name,sensor,owner` header, empty fields are missing values
+    ///
+    pub fn from_csv(data: &str) -> Result<Self> {
+        let mut rdr = ReaderBuilder::new()
+            .trim(Trim::All)
+            .from_reader(data.as_bytes());
+        let sites = rdr.deserialize().collect::<Result<Vec<Site>, _>>()?;
+        Ok(SicRegistry { sites })
+    }
+
+    /// Check the registry against the official list
+    ///
+    pub fn validate(&self, areas: &[Area]) -> Vec<RegistryIssue> {
+        let mut issues = vec![];
+        let mut seen: BTreeMap<DataSourceId, Vec<String>> = BTreeMap::new();
+
+        for site in &self.sites {
+            let Ok(id) = site.id() else {
+                issues.push(RegistryIssue::BadSac {
+                    sac: site.sac.to_owned(),
+                    name: site.name.to_owned(),
+                });
+                continue;
+            };
+            if id.resolve(areas).is_none() {
+                issues.push(RegistryIssue::UnknownSac {
+                    id,
+                    name: site.name.to_owned(),
+                });
+            }
+            seen.entry(id).or_default().push(site.name.to_owned());
+        }
+        issues.extend(
+            seen.into_iter()
+                .filter(|(_, names)| names.len() > 1)
+                .map(|(id, names)| RegistryIssue::Duplicate { id, names }),
+        );
+        issues
+    }
+
+    /// Merge every valid site with its SAC from the official list, sorted by SAC/SIC
+    ///
+    pub fn merge(&self, areas: &[Area]) -> Vec<MergedSite> {
+        let mut res: Vec<_> = self
+            .sites
+            .iter()
+            .filter_map(|site| {
+                let id = site.id().ok()?;
+                let found = lookup_sac(areas, id.sac);
+                Some(MergedSite {
+                    id,
+                    area: found.map(|(a, _)| a.name()),
+                    label: found.map(|(_, l)| l.to_owned()),
+                    name: site.name.to_owned(),
+                    sensor: site.sensor.clone(),
+                    owner: site.owner.clone(),
+                })
+            })
+            .collect();
+        res.sort_by_key(|s| s.id);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{to_lines, Rows};
+
+    fn areas() -> Vec<Area> {
+        let mut a = Area::new("Asia");
+        a.add("94", "Vietnam");
+        vec![a]
+    }
+
+    const TOML: &str = r#"
+[[site]]
+sac = "94"
+sic = 1
+name = "Noi Bai"
+sensor = "PSR/SSR"
+owner = "VATM"
+
+[[site]]
+sac = "12"
+sic = 3
+name = "Nowhere"
+"#;
+
+    const YAML: &str = r#"
+site:
+  - sac: "94"
+    sic: 1
+    name: Noi Bai
+    sensor: PSR/SSR
+    owner: VATM
+  - sac: "12"
+    sic: 3
+    name: Nowhere
+"#;
+
+    const CSV: &str = "SAC,SIC,Name,Sensor,Owner\n94,1,Noi Bai,PSR/SSR,VATM\n12,3,Nowhere,,\n";
+
+    #[test]
+    fn test_registry_formats() {
+        let toml: SicRegistry = toml::from_str(TOML).unwrap();
+        let yaml: SicRegistry = serde_yaml::from_str(YAML).unwrap();
+        let csv = SicRegistry::from_csv(CSV).unwrap();
+
+        assert_eq!(2, toml.sites.len());
+        assert_eq!(Some("VATM".to_owned()), toml.sites[0].owner);
+        assert_eq!(None, toml.sites[1].sensor);
+        assert_eq!(toml, yaml);
+        assert_eq!(toml, csv);
+    }
+
+    #[test]
+    fn test_registry_load() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, data) in [("r.toml", TOML), ("r.yml", YAML), ("r.csv", CSV)] {
+            let path = dir.path().join(name);
+            fs::write(&path, data).unwrap();
+            assert_eq!(2, SicRegistry::load(&path).unwrap().sites.len(), "{}", name);
+        }
+        assert!(SicRegistry::load(Path::new("README.md")).is_err());
+    }
+
+    #[test]
+    fn test_registry_validate() {
+        let mut reg: SicRegistry = toml::from_str(TOML).unwrap();
+        reg.sites.push(Site {
+            sac: "94".to_owned(),
+            sic: 1,
+            name: "Noi Bai 2".to_owned(),
+            ..Default::default()
+        });
+        reg.sites.push(Site {
+            sac: "XYZ".to_owned(),
+            sic: 1,
+            name: "Broken".to_owned(),
+            ..Default::default()
+        });
+
+        let issues = reg.validate(&areas());
+        assert_eq!(
+            vec![
+                "Nowhere: 12/03 has an unknown SAC",
+                "Broken: invalid SAC XYZ",
+                "94/01 used by Noi Bai, Noi Bai 2",
+            ],
+            issues.iter().map(|i| i.to_string()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_registry_merge() {
+        let reg: SicRegistry = toml::from_str(TOML).unwrap();
+        let merged = reg.merge(&areas());
+
+        assert_eq!(2, merged.len());
+        assert_eq!(DataSourceId::new(0x12, 3), merged[0].id);
+        assert_eq!(None, merged[0].area);
+        assert_eq!(Some("Asia".to_owned()), merged[1].area);
+        assert_eq!(Some("Vietnam".to_owned()), merged[1].label);
+        assert_eq!("Noi Bai", merged[1].name);
+    }
+
+    #[test]
+    fn test_sites_envelope() {
+        let reg: SicRegistry = toml::from_str(TOML).unwrap();
+        let sites = reg.merge(&areas());
+        let env = Sites::new(&sites, Utc::now()).unwrap();
+
+        assert_eq!(SCHEMA_VERSION, env.schema_version);
+        assert_eq!(PAGE, env.source_url);
+        assert_eq!(sites, env.sites);
+        assert!(env.hash.starts_with("sha256:"));
+        assert_eq!(env.hash, Sites::new(&sites, Utc::now()).unwrap().hash);
+    }
+
+    #[test]
+    fn test_merged_site_display_cells() {
+        let reg: SicRegistry = toml::from_str(TOML).unwrap();
+        let sites = reg.merge(&areas());
+
+        assert_eq!(
+            "12/03 = Nowhere in unknown SAC\n\
+            94/01 = Noi Bai (PSR/SSR, VATM) in Asia / Vietnam",
+            to_lines(&sites)
+        );
+        let csv = Rows::new("Sites", &sites)
+            .to_csv(&Default::default())
+            .unwrap();
+        assert!(csv.contains(r#""Asia","94","01","Vietnam","Noi Bai","PSR/SSR","VATM","148/1""#));
+    }
+}
@@ -0,0 +1,426 @@
+//! Module dealing with spreadsheet output, Excel (`.xlsx`) and OpenDocument (`.ods`)
+//!
+//! Both have one sheet per area plus an "All" sheet with every code, a styled and frozen
+//! header row and all cells stored as text so that codes like `00` or `0A` are kept as-is.
+//!
+
+use std::io::{Cursor, Write};
+
+use anyhow::Result;
+use log::trace;
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::Writer;
+use rust_xlsxwriter::{Color, Format as XlsxFormat, Workbook, Worksheet};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::core::{Area, SAC};
+
+/// Name of the sheet with all areas
+///
+const ALL: &str = "All";
+
+/// Columns of the per-area sheets
+///
+const AREA_HEADER: [&str; 3] = ["SAC", "Label", "Binary"];
+
+/// Columns of the "All" sheet
+///
+const ALL_HEADER: [&str; 4] = ["Area", "SAC", "Label", "Binary"];
+
+/// Make the area name acceptable as a sheet name: at most 31 characters and none of `[]:*?/\`
+///
+fn sheet_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '[' | ']' | ':' | '*' | '?' | '/' | '\\' => '-',
+            _ => c,
+        })
+        .take(31)
+        .collect()
+}
+
+/// Sanitized names for all the sheets, made unique (ignoring case, like Excel does) by adding
+/// " (2)", " (3)"… and still at most 31 characters
+///
+fn sheet_names(sheets: &[Sheet]) -> Vec<String> {
+    let mut seen: Vec<String> = vec![];
+    sheets
+        .iter()
+        .map(|(name, _)| {
+            let base = match sheet_name(name) {
+                n if n.trim().is_empty() => "Sheet".to_owned(),
+                n => n,
+            };
+            let mut res = base.clone();
+            let mut n = 1;
+            while seen.contains(&res.to_lowercase()) {
+                n += 1;
+                let suffix = format!(" ({})", n);
+                let keep = 31 - suffix.chars().count();
+                res = base.chars().take(keep).collect::<String>() + &suffix;
+            }
+            seen.push(res.to_lowercase());
+            res
+        })
+        .collect()
+}
+
+/// Name and rows of one sheet, header first
+///
+pub(crate) type Sheet = (String, Vec<Vec<String>>);
+
+/// One sheet worth of data, header first
+///
+fn sheets(areas: &[Area]) -> Vec<Sheet> {
+    let binary = |code: &str| {
+        SAC::try_from(code)
+            .unwrap_or_default()
+            .binary()
+            .unwrap_or_default()
+    };
+
+    let mut all = vec![ALL_HEADER.iter().map(|s| s.to_string()).collect()];
+    let mut sheets: Vec<_> = areas
+        .iter()
+        .map(|area| {
+            let mut rows = vec![AREA_HEADER.iter().map(|s| s.to_string()).collect()];
+            area.iter().for_each(|(code, label)| {
+                rows.push(vec![code.to_owned(), label.to_owned(), binary(code)]);
+                all.push(vec![
+                    area.name(),
+                    code.to_owned(),
+                    label.to_owned(),
+                    binary(code),
+                ]);
+            });
+            (sheet_name(&area.name()), rows)
+        })
+        .collect();
+    sheets.insert(0, (ALL.to_owned(), all));
+    sheets
+}
+
+/// Fill one Excel worksheet
+///
+fn fill_xlsx(ws: &mut Worksheet, name: &str, rows: &[Vec<String>]) -> Result<()> {
+    let header = XlsxFormat::new()
+        .set_bold()
+        .set_background_color(Color::RGB(0xDDDDDD))
+        .set_num_format("@");
+    let text = XlsxFormat::new().set_num_format("@");
+
+    ws.set_name(name)?;
+    for (r, row) in rows.iter().enumerate() {
+        let fmt = if r == 0 { &header } else { &text };
+        for (c, cell) in row.iter().enumerate() {
+            ws.write_string_with_format(r as u32, c as u16, cell, fmt)?;
+        }
+    }
+    ws.set_freeze_panes(1, 0)?;
+    ws.autofit();
+    Ok(())
+}
+
+/// Generate an Excel workbook
+///
+pub fn to_xlsx(areas: &[Area]) -> Result<Vec<u8>> {
+    trace!("Generating xlsx output…");
+    xlsx_from_sheets(&sheets(areas))
+}
+
+/// Generate an Excel workbook from any sheets
+///
+pub(crate) fn xlsx_from_sheets(sheets: &[Sheet]) -> Result<Vec<u8>> {
+    let mut wb = Workbook::new();
+
+    for (name, (_, rows)) in sheet_names(sheets).iter().zip(sheets) {
+        fill_xlsx(wb.add_worksheet(), name, rows)?;
+    }
+    Ok(wb.save_to_buffer()?)
+}
+
+const ODS_MIMETYPE: &str = "application/vnd.oasis.opendocument.spreadsheet";
+
+const ODS_MANIFEST: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
+ <manifest:file-entry manifest:full-path="/" manifest:version="1.2" manifest:media-type="application/vnd.oasis.opendocument.spreadsheet"/>
+ <manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+ <manifest:file-entry manifest:full-path="settings.xml" manifest:media-type="text/xml"/>
+</manifest:manifest>
+"##;
+
+const ODS_STYLES: &str = r##"<office:automatic-styles>
+ <style:style style:name="hdr" style:family="table-cell">
+  <style:table-cell-properties fo:background-color="#dddddd"/>
+  <style:text-properties fo:font-weight="bold"/>
+ </style:style>
+</office:automatic-styles>"##;
+
+/// Generate `content.xml` with all the sheets
+///
+fn ods_content(names: &[String], sheets: &[Sheet]) -> Result<Vec<u8>> {
+    let mut wtr = Writer::new(vec![]);
+
+    wtr.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    wtr.create_element("office:document-content")
+        .with_attributes([
+            (
+                "xmlns:office",
+                "urn:oasis:names:tc:opendocument:xmlns:office:1.0",
+            ),
+            (
+                "xmlns:style",
+                "urn:oasis:names:tc:opendocument:xmlns:style:1.0",
+            ),
+            (
+                "xmlns:table",
+                "urn:oasis:names:tc:opendocument:xmlns:table:1.0",
+            ),
+            (
+                "xmlns:text",
+                "urn:oasis:names:tc:opendocument:xmlns:text:1.0",
+            ),
+            (
+                "xmlns:fo",
+                "urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0",
+            ),
+            ("office:version", "1.2"),
+        ])
+        .write_inner_content(|w| {
+            w.get_mut().write_all(ODS_STYLES.as_bytes())?;
+            w.create_element("office:body").write_inner_content(|w| {
+                w.create_element("office:spreadsheet")
+                    .write_inner_content(|w| {
+                        names
+                            .iter()
+                            .zip(sheets)
+                            .try_for_each(|(name, (_, rows))| ods_table(w, name, rows))
+                    })?;
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+    Ok(wtr.into_inner())
+}
+
+/// One sheet, the first row is the header
+///
+fn ods_table(w: &mut Writer<Vec<u8>>, name: &str, rows: &[Vec<String>]) -> std::io::Result<()> {
+    w.create_element("table:table")
+        .with_attribute(("table:name", name))
+        .write_inner_content(|w| {
+            for (r, row) in rows.iter().enumerate() {
+                if r == 0 {
+                    w.create_element("table:table-header-rows")
+                        .write_inner_content(|w| ods_row(w, row, true))?;
+                } else {
+                    ods_row(w, row, false)?;
+                }
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
+
+/// One row of text cells
+///
+fn ods_row(w: &mut Writer<Vec<u8>>, row: &[String], header: bool) -> std::io::Result<()> {
+    w.create_element("table:table-row")
+        .write_inner_content(|w| {
+            for cell in row {
+                let elem = w
+                    .create_element("table:table-cell")
+                    .with_attribute(("office:value-type", "string"));
+                let elem = if header {
+                    elem.with_attribute(("table:style-name", "hdr"))
+                } else {
+                    elem
+                };
+                elem.write_inner_content(|w| {
+                    w.create_element("text:p")
+                        .write_text_content(BytesText::new(cell))?;
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
+
+/// Generate `settings.xml`, only used to freeze the header row of every sheet
+///
+fn ods_settings(names: &[String]) -> String {
+    let tables = names
+        .iter()
+        .map(|name| {
+            format!(
+                r##"<config:config-item-map-entry config:name="{}">
+<config:config-item config:name="VerticalSplitMode" config:type="short">2</config:config-item>
+<config:config-item config:name="VerticalSplitPosition" config:type="int">1</config:config-item>
+<config:config-item config:name="ActiveSplitRange" config:type="short">2</config:config-item>
+<config:config-item config:name="PositionTop" config:type="int">0</config:config-item>
+<config:config-item config:name="PositionBottom" config:type="int">1</config:config-item>
+</config:config-item-map-entry>"##,
+                html_escape::encode_double_quoted_attribute(name)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        r##"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-settings xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:config="urn:oasis:names:tc:opendocument:xmlns:config:1.0" office:version="1.2">
+<office:settings>
+<config:config-item-set config:name="ooo:view-settings">
+<config:config-item-map-indexed config:name="Views">
+<config:config-item-map-entry>
+<config:config-item-map-named config:name="Tables">
+{}
+</config:config-item-map-named>
+</config:config-item-map-entry>
+</config:config-item-map-indexed>
+</config:config-item-set>
+</office:settings>
+</office:document-settings>
+"##,
+        tables
+    )
+}
+
+/// Generate an OpenDocument spreadsheet
+///
+pub fn to_ods(areas: &[Area]) -> Result<Vec<u8>> {
+    trace!("Generating ods output…");
+    ods_from_sheets(&sheets(areas))
+}
+
+/// Generate an OpenDocument spreadsheet from any sheets
+///
+pub(crate) fn ods_from_sheets(sheets: &[Sheet]) -> Result<Vec<u8>> {
+    let names = sheet_names(sheets);
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+
+    // `mimetype` must be first and not compressed
+    //
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(ODS_MIMETYPE.as_bytes())?;
+    zip.start_file("META-INF/manifest.xml", deflated)?;
+    zip.write_all(ODS_MANIFEST.as_bytes())?;
+    zip.start_file("content.xml", deflated)?;
+    zip.write_all(&ods_content(&names, sheets)?)?;
+    zip.start_file("settings.xml", deflated)?;
+    zip.write_all(ods_settings(&names).as_bytes())?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use zip::ZipArchive;
+
+    use super::*;
+
+    fn sample() -> Vec<Area> {
+        let mut a = Area::new("Asia/Pacific");
+        a.add("00", "Nowhere");
+        a.add("94", "Vietnam & co");
+        vec![a]
+    }
+
+    fn read(data: Vec<u8>, name: &str) -> String {
+        let mut zip = ZipArchive::new(Cursor::new(data)).unwrap();
+        let mut s = String::new();
+        zip.by_name(name).unwrap().read_to_string(&mut s).unwrap();
+        s
+    }
+
+    #[test]
+    fn test_sheet_name() {
+        assert_eq!("Asia-Pacific", sheet_name("Asia/Pacific"));
+        assert_eq!(31, sheet_name(&"x".repeat(40)).len());
+    }
+
+    #[test]
+    fn test_sheet_names() {
+        let long = "x".repeat(40);
+        let names = [
+            "All",
+            "all",
+            "Asia/Pacific",
+            "Asia-Pacific",
+            &long,
+            &long,
+            "",
+        ];
+        let sheets: Vec<Sheet> = names.iter().map(|n| (n.to_string(), vec![])).collect();
+
+        let res = sheet_names(&sheets);
+        assert_eq!("All", res[0]);
+        assert_eq!("all (2)", res[1]);
+        assert_eq!("Asia-Pacific", res[2]);
+        assert_eq!("Asia-Pacific (2)", res[3]);
+        assert_eq!("x".repeat(31), res[4]);
+        assert_eq!(format!("{} (2)", "x".repeat(27)), res[5]);
+        assert_eq!("Sheet", res[6]);
+    }
+
+    #[test]
+    fn test_to_xlsx_ods_duplicates() {
+        let mut areas = sample();
+        areas.push(Area::new("All"));
+        areas.push(Area::new("Asia:Pacific"));
+
+        let data = to_xlsx(&areas).unwrap();
+        let wb = read(data, "xl/workbook.xml");
+        assert!(wb.contains(r#"name="All (2)""#));
+        assert!(wb.contains(r#"name="Asia-Pacific (2)""#));
+
+        let content = read(to_ods(&areas).unwrap(), "content.xml");
+        assert!(content.contains(r#"<table:table table:name="All (2)">"#));
+    }
+
+    #[test]
+    fn test_sheets() {
+        let s = sheets(&sample());
+
+        assert_eq!(2, s.len());
+        assert_eq!("All", s[0].0);
+        assert_eq!(vec!["Area", "SAC", "Label", "Binary"], s[0].1[0]);
+        assert_eq!(vec!["Asia/Pacific", "00", "Nowhere", "00000000"], s[0].1[1]);
+        assert_eq!("Asia-Pacific", s[1].0);
+        assert_eq!(3, s[1].1.len());
+    }
+
+    #[test]
+    fn test_to_xlsx() {
+        let data = to_xlsx(&sample()).unwrap();
+
+        let wb = read(data.clone(), "xl/workbook.xml");
+        assert!(wb.contains(r#"name="All""#));
+        assert!(wb.contains(r#"name="Asia-Pacific""#));
+        let strings = read(data.clone(), "xl/sharedStrings.xml");
+        assert!(strings.contains("<t>00</t>"));
+        let sheet = read(data, "xl/worksheets/sheet2.xml");
+        assert!(sheet.contains(r#"state="frozen""#));
+    }
+
+    #[test]
+    fn test_to_ods() {
+        let data = to_ods(&sample()).unwrap();
+
+        assert_eq!(ODS_MIMETYPE, read(data.clone(), "mimetype"));
+        let content = read(data.clone(), "content.xml");
+        assert!(content.contains(r#"<table:table table:name="Asia-Pacific">"#));
+        assert!(content.contains(
+            r#"<table:table-cell office:value-type="string"><text:p>00</text:p></table:table-cell>"#
+        ));
+        assert!(content.contains("Vietnam &amp; co"));
+        let settings = read(data, "settings.xml");
+        assert!(settings.contains(r#"config:name="All""#));
+    }
+}
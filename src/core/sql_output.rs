@@ -0,0 +1,390 @@
+//! Module dealing with SQL script output for PostgreSQL
+//!
+//! The script creates the tables if needed and upserts every area and code so it can be run
+//! any number of times.  Codes and areas no longer in the snapshot are deleted in the same
+//! transaction so the tables always mirror the page.  The schema is stable and described in
+//! [`PG_SCHEMA`]:
+//!
+//! - `sac_source`: where and when the snapshot was retrieved, with the date of the page itself
+//! - `sac_areas`: one row per area, keyed by its slug, with the notes published with its table
+//! - `sac_codes`: one row per code, keyed by area and code as published; `value` is the
+//!   numeric code for single codes, `lo`/`hi` are set for ranges
+//!
+//! Categories work the same way with [`PG_CATEGORIES_SCHEMA`]:
+//!
+//! - `asterix_categories`: one row per category, keyed by its number
+//! - `asterix_category_links`: documents of a category, keyed by category and URL
+//!
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use log::trace;
+
+use crate::core::{Area, Category, PAGE, SAC};
+
+/// DDL for the PostgreSQL tables
+///
+pub const PG_SCHEMA: &str = r##"CREATE TABLE IF NOT EXISTS sac_source (
+    source_url TEXT PRIMARY KEY,
+    retrieved_at TIMESTAMPTZ NOT NULL,
+    page_updated TEXT
+);
+
+CREATE TABLE IF NOT EXISTS sac_areas (
+    slug TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    notes TEXT[] NOT NULL DEFAULT '{}'
+);
+ALTER TABLE sac_areas ADD COLUMN IF NOT EXISTS notes TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE TABLE IF NOT EXISTS sac_codes (
+    area_slug TEXT NOT NULL REFERENCES sac_areas (slug),
+    code TEXT NOT NULL,
+    value SMALLINT,
+    lo SMALLINT,
+    hi SMALLINT,
+    label TEXT NOT NULL,
+    PRIMARY KEY (area_slug, code)
+);
+"##;
+
+/// DDL for the PostgreSQL tables of the categories
+///
+pub const PG_CATEGORIES_SCHEMA: &str = r##"CREATE TABLE IF NOT EXISTS asterix_categories (
+    number SMALLINT PRIMARY KEY,
+    title TEXT NOT NULL,
+    edition TEXT,
+    date TEXT
+);
+
+CREATE TABLE IF NOT EXISTS asterix_category_links (
+    number SMALLINT NOT NULL REFERENCES asterix_categories (number),
+    url TEXT NOT NULL,
+    title TEXT NOT NULL,
+    PRIMARY KEY (number, url)
+);
+"##;
+
+/// Quote a string literal
+///
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Optional number or NULL
+///
+fn number<T: ToString>(v: Option<T>) -> String {
+    v.map(|v| v.to_string())
+        .unwrap_or_else(|| "NULL".to_owned())
+}
+
+/// Optional string literal or NULL
+///
+fn text(v: Option<&str>) -> String {
+    v.map(quote).unwrap_or_else(|| "NULL".to_owned())
+}
+
+/// Comma-separated list of quoted strings for `IN (…)`
+///
+fn list<'a>(items: impl Iterator<Item = &'a str>) -> String {
+    items.map(quote).collect::<Vec<_>>().join(", ")
+}
+
+/// `TEXT[]` literal
+///
+fn array(items: &[String]) -> String {
+    match items.is_empty() {
+        true => "'{}'::TEXT[]".to_owned(),
+        false => format!("ARRAY[{}]", list(items.iter().map(|s| s.as_str()))),
+    }
+}
+
+/// Generate a PostgreSQL script with the schema, idempotent `INSERT … ON CONFLICT` for
+/// every area and code and `DELETE` for what is not there anymore.  `updated` is the date
+/// published on the page, if any.
+///
+/// Fails if two areas have the same slug as they would be merged into one row.
+///
+pub fn to_sql(areas: &[Area], retrieved: DateTime<Utc>, updated: Option<&str>) -> Result<String> {
+    trace!("Generating sql output…");
+
+    // Nothing scraped is a broken page or input, not an empty list
+    //
+    if areas.is_empty() {
+        return Err(anyhow!(
+            "no area, refusing to generate a script emptying the tables"
+        ));
+    }
+
+    let mut slugs: HashMap<String, String> = HashMap::new();
+    for area in areas {
+        if let Some(other) = slugs.insert(area.slug(), area.name()) {
+            return Err(anyhow!(
+                "areas {} and {} have the same slug {}",
+                other,
+                area.name(),
+                area.slug()
+            ));
+        }
+    }
+
+    let mut out = vec![
+        format!("-- SAC codes from {PAGE}"),
+        format!("-- Retrieved on {}", retrieved.to_rfc3339()),
+        String::new(),
+        "BEGIN;".to_owned(),
+        String::new(),
+        PG_SCHEMA.to_owned(),
+        format!(
+            "INSERT INTO sac_source (source_url, retrieved_at, page_updated) VALUES ({}, {}, {})\n    \
+            ON CONFLICT (source_url) DO UPDATE SET retrieved_at = EXCLUDED.retrieved_at, \
+            page_updated = EXCLUDED.page_updated;",
+            quote(PAGE),
+            quote(&retrieved.to_rfc3339()),
+            text(updated)
+        ),
+        String::new(),
+    ];
+
+    for area in areas {
+        let slug = quote(&area.slug());
+        out.push(format!(
+            "INSERT INTO sac_areas (slug, name, notes) VALUES ({}, {}, {})\n    \
+            ON CONFLICT (slug) DO UPDATE SET name = EXCLUDED.name, notes = EXCLUDED.notes;",
+            slug,
+            quote(&area.name()),
+            array(area.notes())
+        ));
+        for (code, label) in area {
+            let (value, lo, hi) = match SAC::try_from(code.as_str()).unwrap_or_default() {
+                SAC::Hex(s) => (u8::from_str_radix(&s, 16).ok(), None, None),
+                SAC::Range { lo, hi } => (None, Some(lo), Some(hi)),
+                SAC::Empty => (None, None, None),
+            };
+            out.push(format!(
+                "INSERT INTO sac_codes (area_slug, code, value, lo, hi, label) \
+                VALUES ({}, {}, {}, {}, {}, {})\n    \
+                ON CONFLICT (area_slug, code) DO UPDATE SET value = EXCLUDED.value, \
+                lo = EXCLUDED.lo, hi = EXCLUDED.hi, label = EXCLUDED.label;",
+                slug,
+                quote(code),
+                number(value),
+                number(lo),
+                number(hi),
+                quote(label)
+            ));
+        }
+        match area.is_empty() {
+            true => out.push(format!("DELETE FROM sac_codes WHERE area_slug = {};", slug)),
+            false => out.push(format!(
+                "DELETE FROM sac_codes WHERE area_slug = {} AND code NOT IN ({});",
+                slug,
+                list(area.iter().map(|(code, _)| code.as_str()))
+            )),
+        }
+        out.push(String::new());
+    }
+
+    // Areas gone from the page
+    //
+    let all: Vec<_> = areas.iter().map(|a| a.slug()).collect();
+    let all = list(all.iter().map(|s| s.as_str()));
+    out.extend([
+        format!("DELETE FROM sac_codes WHERE area_slug NOT IN ({});", all),
+        format!("DELETE FROM sac_areas WHERE slug NOT IN ({});", all),
+        String::new(),
+    ]);
+    out.push("COMMIT;\n".to_owned());
+    Ok(out.join("\n"))
+}
+
+/// Generate a PostgreSQL script for the categories, same idea as [`to_sql`]: upsert every
+/// category and link then delete what is not there anymore.
+///
+pub fn categories_to_sql(cats: &[Category], retrieved: DateTime<Utc>) -> Result<String> {
+    trace!("Generating categories sql output…");
+
+    if cats.is_empty() {
+        return Err(anyhow!(
+            "no category, refusing to generate a script emptying the tables"
+        ));
+    }
+
+    let mut out = vec![
+        format!("-- ASTERIX categories from {PAGE}"),
+        format!("-- Retrieved on {}", retrieved.to_rfc3339()),
+        String::new(),
+        "BEGIN;".to_owned(),
+        String::new(),
+        PG_CATEGORIES_SCHEMA.to_owned(),
+    ];
+
+    for cat in cats {
+        out.push(format!(
+            "INSERT INTO asterix_categories (number, title, edition, date) \
+            VALUES ({}, {}, {}, {})\n    \
+            ON CONFLICT (number) DO UPDATE SET title = EXCLUDED.title, \
+            edition = EXCLUDED.edition, date = EXCLUDED.date;",
+            cat.number,
+            quote(&cat.title),
+            text(cat.edition.as_deref()),
+            text(cat.date.as_deref())
+        ));
+        for link in &cat.links {
+            out.push(format!(
+                "INSERT INTO asterix_category_links (number, url, title) VALUES ({}, {}, {})\n    \
+                ON CONFLICT (number, url) DO UPDATE SET title = EXCLUDED.title;",
+                cat.number,
+                quote(&link.url),
+                quote(&link.title)
+            ));
+        }
+        match cat.links.is_empty() {
+            true => out.push(format!(
+                "DELETE FROM asterix_category_links WHERE number = {};",
+                cat.number
+            )),
+            false => out.push(format!(
+                "DELETE FROM asterix_category_links WHERE number = {} AND url NOT IN ({});",
+                cat.number,
+                list(cat.links.iter().map(|l| l.url.as_str()))
+            )),
+        }
+        out.push(String::new());
+    }
+
+    // Categories gone from the page
+    //
+    let all = cats
+        .iter()
+        .map(|c| c.number.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    out.extend([
+        format!(
+            "DELETE FROM asterix_category_links WHERE number NOT IN ({});",
+            all
+        ),
+        format!(
+            "DELETE FROM asterix_categories WHERE number NOT IN ({});",
+            all
+        ),
+        String::new(),
+    ]);
+    out.push("COMMIT;\n".to_owned());
+    Ok(out.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::core::Link;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!("'Cote d''Ivoire'", quote("Cote d'Ivoire"));
+    }
+
+    #[test]
+    fn test_to_sql() {
+        let mut a = Area::new("Africa");
+        a.add("94", "Cote d'Ivoire");
+        a.add("A0...C3", "Reserved");
+        a.add_note("Codes 00 to 03 are reserved.");
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+
+        let sql = to_sql(&[a], date, Some("12 March 2024")).unwrap();
+
+        assert!(sql.contains("-- Retrieved on 2023-04-01T12:00:00+00:00"));
+        assert!(sql.contains("CREATE TABLE IF NOT EXISTS sac_codes"));
+        assert!(sql.contains(
+            "VALUES ('https://www.eurocontrol.int/asterix', '2023-04-01T12:00:00+00:00', \
+            '12 March 2024')"
+        ));
+        assert!(sql.contains("VALUES ('africa', 'Africa', ARRAY['Codes 00 to 03 are reserved.'])"));
+        assert!(sql.contains("VALUES ('africa', '94', 148, NULL, NULL, 'Cote d''Ivoire')"));
+        assert!(sql.contains("VALUES ('africa', 'A0...C3', NULL, 160, 195, 'Reserved')"));
+        assert!(sql.contains(
+            "DELETE FROM sac_codes WHERE area_slug = 'africa' AND code NOT IN ('94', 'A0...C3');"
+        ));
+        assert!(sql.contains("DELETE FROM sac_codes WHERE area_slug NOT IN ('africa');"));
+        assert!(sql.contains("DELETE FROM sac_areas WHERE slug NOT IN ('africa');"));
+        assert!(sql.trim_end().ends_with("COMMIT;"));
+
+        // Deletes come after the upserts
+        //
+        let upsert = sql.find("INSERT INTO sac_codes").unwrap();
+        assert!(sql.find("DELETE FROM sac_codes").unwrap() > upsert);
+    }
+
+    #[test]
+    fn test_to_sql_empty_area() {
+        let a = Area::new("Africa");
+        let sql = to_sql(&[a], Utc::now(), None).unwrap();
+
+        assert!(sql.contains("DELETE FROM sac_codes WHERE area_slug = 'africa';"));
+        assert!(sql.contains("VALUES ('africa', 'Africa', '{}'::TEXT[])"));
+        assert!(sql.contains("', NULL)\n"));
+    }
+
+    #[test]
+    fn test_to_sql_no_area() {
+        let err = to_sql(&[], Utc::now(), None).unwrap_err();
+
+        assert!(err.to_string().contains("no area"));
+    }
+
+    #[test]
+    fn test_to_sql_slug_collision() {
+        let areas = [Area::new("Asia & Pacific"), Area::new("Asia/Pacific")];
+        let err = to_sql(&areas, Utc::now(), None).unwrap_err();
+
+        assert!(err.to_string().contains("same slug asia-pacific"));
+    }
+
+    #[test]
+    fn test_categories_to_sql() {
+        let cats = [
+            Category {
+                number: 1,
+                title: "Monoradar Target Reports".to_owned(),
+                edition: Some("1.4".to_owned()),
+                date: None,
+                links: vec![Link {
+                    title: "Specification".to_owned(),
+                    url: "https://www.eurocontrol.int/cat001.pdf".to_owned(),
+                }],
+            },
+            Category {
+                number: 62,
+                title: "SDPS Track Messages".to_owned(),
+                ..Default::default()
+            },
+        ];
+        let sql = categories_to_sql(&cats, Utc::now()).unwrap();
+
+        assert!(sql.contains("CREATE TABLE IF NOT EXISTS asterix_categories"));
+        assert!(sql.contains("VALUES (1, 'Monoradar Target Reports', '1.4', NULL)"));
+        assert!(
+            sql.contains("VALUES (1, 'https://www.eurocontrol.int/cat001.pdf', 'Specification')")
+        );
+        assert!(sql.contains(
+            "DELETE FROM asterix_category_links WHERE number = 1 AND url NOT IN \
+            ('https://www.eurocontrol.int/cat001.pdf');"
+        ));
+        assert!(sql.contains("DELETE FROM asterix_category_links WHERE number = 62;"));
+        assert!(sql.contains("DELETE FROM asterix_categories WHERE number NOT IN (1, 62);"));
+        assert!(sql.trim_end().ends_with("COMMIT;"));
+    }
+
+    #[test]
+    fn test_categories_to_sql_none() {
+        let err = categories_to_sql(&[], Utc::now()).unwrap_err();
+
+        assert!(err.to_string().contains("no category"));
+    }
+}
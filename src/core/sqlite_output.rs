@@ -0,0 +1,375 @@
+//! Module dealing with SQLite output
+//!
+//! Every run adds a new snapshot to the database so history is kept, the schema is:
+//!
+//! - `snapshots`: one row per run with retrieval date, source and page date
+//! - `areas`: areas for a given snapshot
+//! - `area_notes`: notes published with the table of an area, in page order
+//! - `sacs`: codes for a given area, `value` is the numeric code (NULL for ranges)
+//! - `ranges`: `lo`/`hi` bounds for codes which are ranges
+//! - `sacs_fts`: FTS5 index on the labels
+//!
+//! Categories have their own snapshots in the same database, see [`SQLITE_CATEGORIES_SCHEMA`],
+//! with the `category_editions` view telling when every edition was first and last seen:
+//!
+//! - `category_snapshots`: one row per run with retrieval date, source and page date
+//! - `categories`: categories for a given snapshot with their edition and date
+//! - `category_links`: documents of a given category
+//!
+
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{debug, trace};
+use rusqlite::{params, Connection};
+
+use crate::core::{Area, Categories, PAGE, SAC};
+
+/// Schema, everything is created only if missing
+///
+pub const SQLITE_SCHEMA: &str = r##"
+CREATE TABLE IF NOT EXISTS snapshots (
+    id INTEGER PRIMARY KEY,
+    retrieved_at TEXT NOT NULL,
+    source_url TEXT NOT NULL,
+    page_updated TEXT
+);
+CREATE TABLE IF NOT EXISTS areas (
+    id INTEGER PRIMARY KEY,
+    snapshot_id INTEGER NOT NULL REFERENCES snapshots(id),
+    name TEXT NOT NULL,
+    slug TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS area_notes (
+    area_id INTEGER NOT NULL REFERENCES areas(id),
+    position INTEGER NOT NULL,
+    note TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS sacs (
+    id INTEGER PRIMARY KEY,
+    area_id INTEGER NOT NULL REFERENCES areas(id),
+    code TEXT NOT NULL,
+    value INTEGER,
+    label TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS ranges (
+    sac_id INTEGER PRIMARY KEY REFERENCES sacs(id),
+    lo INTEGER NOT NULL,
+    hi INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS sacs_value ON sacs(value);
+CREATE VIRTUAL TABLE IF NOT EXISTS sacs_fts USING fts5(label, content='sacs', content_rowid='id');
+"##;
+
+/// Schema for the categories, everything is created only if missing
+///
+pub const SQLITE_CATEGORIES_SCHEMA: &str = r##"
+CREATE TABLE IF NOT EXISTS category_snapshots (
+    id INTEGER PRIMARY KEY,
+    retrieved_at TEXT NOT NULL,
+    source_url TEXT NOT NULL,
+    page_updated TEXT
+);
+CREATE TABLE IF NOT EXISTS categories (
+    id INTEGER PRIMARY KEY,
+    snapshot_id INTEGER NOT NULL REFERENCES category_snapshots(id),
+    number INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    edition TEXT,
+    date TEXT
+);
+CREATE TABLE IF NOT EXISTS category_links (
+    category_id INTEGER NOT NULL REFERENCES categories(id),
+    title TEXT NOT NULL,
+    url TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS categories_number ON categories(number);
+CREATE VIEW IF NOT EXISTS category_editions AS
+    SELECT number, edition, MIN(s.retrieved_at) AS first_seen, MAX(s.retrieved_at) AS last_seen
+    FROM categories JOIN category_snapshots s ON s.id = snapshot_id
+    GROUP BY number, edition;
+"##;
+
+/// Add `column` to `table` in databases created before it existed
+///
+fn add_column(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |r| r.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if !columns.iter().any(|c| c == column) {
+        debug!("adding {}.{}", table, column);
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {};",
+            table, column, decl
+        ))?;
+    }
+    Ok(())
+}
+
+/// Add a new snapshot of `areas` into the SQLite database at `path`, creating it if needed.
+/// `updated` is the date published on the page, if any.
+///
+/// Returns the id of the new snapshot.
+///
+pub fn to_sqlite(
+    areas: &[Area],
+    retrieved: DateTime<Utc>,
+    updated: Option<&str>,
+    path: &Path,
+) -> Result<i64> {
+    trace!("Generating sqlite output…");
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SQLITE_SCHEMA)?;
+    add_column(&conn, "snapshots", "page_updated", "TEXT")?;
+
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO snapshots (retrieved_at, source_url, page_updated) VALUES (?1, ?2, ?3)",
+        params![retrieved.to_rfc3339(), PAGE, updated],
+    )?;
+    let snapshot = tx.last_insert_rowid();
+    debug!("snapshot={}", snapshot);
+
+    for area in areas {
+        tx.execute(
+            "INSERT INTO areas (snapshot_id, name, slug) VALUES (?1, ?2, ?3)",
+            params![snapshot, area.name(), area.slug()],
+        )?;
+        let area_id = tx.last_insert_rowid();
+
+        for (position, note) in area.notes().iter().enumerate() {
+            tx.execute(
+                "INSERT INTO area_notes (area_id, position, note) VALUES (?1, ?2, ?3)",
+                params![area_id, position, note],
+            )?;
+        }
+
+        for (code, label) in area {
+            let sac = SAC::try_from(code.as_str()).unwrap_or_default();
+            let value = match &sac {
+                SAC::Hex(s) => u8::from_str_radix(s, 16).ok(),
+                _ => None,
+            };
+            tx.execute(
+                "INSERT INTO sacs (area_id, code, value, label) VALUES (?1, ?2, ?3, ?4)",
+                params![area_id, code, value, label],
+            )?;
+            let sac_id = tx.last_insert_rowid();
+            tx.execute(
+                "INSERT INTO sacs_fts (rowid, label) VALUES (?1, ?2)",
+                params![sac_id, label],
+            )?;
+            if let SAC::Range { lo, hi } = sac {
+                tx.execute(
+                    "INSERT INTO ranges (sac_id, lo, hi) VALUES (?1, ?2, ?3)",
+                    params![sac_id, lo, hi],
+                )?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(snapshot)
+}
+
+/// Add a new snapshot of the categories into the SQLite database at `path`, creating it if
+/// needed.
+///
+/// Returns the id of the new snapshot.
+///
+pub fn categories_to_sqlite(cats: &Categories, path: &Path) -> Result<i64> {
+    trace!("Generating categories sqlite output…");
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SQLITE_CATEGORIES_SCHEMA)?;
+
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO category_snapshots (retrieved_at, source_url, page_updated) \
+        VALUES (?1, ?2, ?3)",
+        params![
+            cats.retrieved_at.to_rfc3339(),
+            cats.source_url,
+            cats.page_updated
+        ],
+    )?;
+    let snapshot = tx.last_insert_rowid();
+    debug!("snapshot={}", snapshot);
+
+    for cat in &cats.categories {
+        tx.execute(
+            "INSERT INTO categories (snapshot_id, number, title, edition, date) \
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![snapshot, cat.number, cat.title, cat.edition, cat.date],
+        )?;
+        let cat_id = tx.last_insert_rowid();
+
+        for link in &cat.links {
+            tx.execute(
+                "INSERT INTO category_links (category_id, title, url) VALUES (?1, ?2, ?3)",
+                params![cat_id, link.title, link.url],
+            )?;
+        }
+    }
+    tx.commit()?;
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::core::{Category, Link};
+
+    fn sample() -> Vec<Area> {
+        let mut a = Area::new("Asia");
+        a.add("94", "Vietnam");
+        a.add("A0...C3", "Reserved range");
+        a.add_note("Codes 00 to 03 are reserved.");
+        vec![a]
+    }
+
+    #[test]
+    fn test_to_sqlite() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("sac.db");
+
+        let s1 = to_sqlite(&sample(), Utc::now(), None, &db).unwrap();
+        let s2 = to_sqlite(&sample(), Utc::now(), Some("12 March 2024"), &db).unwrap();
+        assert_ne!(s1, s2);
+
+        let conn = Connection::open(&db).unwrap();
+        let n: i64 = conn
+            .query_row("SELECT COUNT(*) FROM snapshots", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(2, n);
+
+        let updated: Option<String> = conn
+            .query_row(
+                "SELECT page_updated FROM snapshots WHERE id = ?1",
+                [s2],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(Some("12 March 2024".to_owned()), updated);
+
+        let note: String = conn
+            .query_row(
+                "SELECT note FROM area_notes JOIN areas ON areas.id = area_id \
+                WHERE snapshot_id = ?1 AND position = 0",
+                [s2],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!("Codes 00 to 03 are reserved.", note);
+
+        let label: String = conn
+            .query_row(
+                "SELECT label FROM sacs WHERE value = 148 AND area_id IN \
+                (SELECT id FROM areas WHERE snapshot_id = ?1)",
+                [s2],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!("Vietnam", label);
+
+        let (lo, hi): (i64, i64) = conn
+            .query_row("SELECT lo, hi FROM ranges LIMIT 1", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((160, 195), (lo, hi));
+
+        let n: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sacs_fts WHERE sacs_fts MATCH 'vietnam'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(2, n);
+    }
+
+    #[test]
+    fn test_to_sqlite_old_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("sac.db");
+
+        // Snapshots table from before `page_updated`
+        //
+        Connection::open(&db)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE snapshots (id INTEGER PRIMARY KEY, retrieved_at TEXT NOT NULL, \
+                source_url TEXT NOT NULL);",
+            )
+            .unwrap();
+        let id = to_sqlite(&sample(), Utc::now(), Some("12 March 2024"), &db).unwrap();
+
+        let conn = Connection::open(&db).unwrap();
+        let updated: String = conn
+            .query_row(
+                "SELECT page_updated FROM snapshots WHERE id = ?1",
+                [id],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!("12 March 2024", updated);
+    }
+
+    #[test]
+    fn test_categories_to_sqlite() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("sac.db");
+
+        let cat = |edition: &str| Category {
+            number: 62,
+            title: "SDPS Track Messages".to_owned(),
+            edition: Some(edition.to_owned()),
+            date: None,
+            links: vec![Link {
+                title: "Specification".to_owned(),
+                url: "https://www.eurocontrol.int/cat062.pdf".to_owned(),
+            }],
+        };
+        let d1 = Utc.with_ymd_and_hms(2023, 1, 1, 12, 0, 0).unwrap();
+        let d2 = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+
+        // Same database as the SAC codes
+        //
+        to_sqlite(&sample(), d1, None, &db).unwrap();
+        let s1 = categories_to_sqlite(&Categories::new(&[cat("1.18")], d1).unwrap(), &db).unwrap();
+        let s2 = categories_to_sqlite(&Categories::new(&[cat("1.19")], d2).unwrap(), &db).unwrap();
+        assert_ne!(s1, s2);
+
+        let conn = Connection::open(&db).unwrap();
+        let url: String = conn
+            .query_row(
+                "SELECT url FROM category_links JOIN categories c ON c.id = category_id \
+                WHERE snapshot_id = ?1",
+                [s2],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!("https://www.eurocontrol.int/cat062.pdf", url);
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT edition, first_seen FROM category_editions WHERE number = 62 ORDER BY 2",
+            )
+            .unwrap();
+        let editions: Vec<(String, String)> = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                ("1.18".to_owned(), d1.to_rfc3339()),
+                ("1.19".to_owned(), d2.to_rfc3339())
+            ],
+            editions
+        );
+    }
+}
@@ -0,0 +1,346 @@
+//! Module with a generic model of the tables on the page
+//!
+//! Every table is read with the column names from its header row and each row maps these
+//! names to the cell text, so new or reordered columns are kept instead of being lost.  An `Area`
+//! is then built from the SAC and label columns, whatever their position.
+//!
+
+use log::{debug, trace, warn};
+use regex::Regex;
+use scraper::{ElementRef, Selector};
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    cell_text, decode_text, is_header_row, parse_tr, row_cells, row_html, Area, ScrapeOptions,
+};
+
+/// One row, cells with the name of their column
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Row {
+    /// `(column, text)` in page order
+    pub cells: Vec<(String, String)>,
+}
+
+impl Row {
+    /// Text of the cell in `column`
+    ///
+    pub fn get(&self, column: &str) -> Option<&str> {
+        self.cells
+            .iter()
+            .find(|(c, _)| c == column)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// One table of the page, named after its tab
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Table {
+    /// Name of the tab
+    pub name: String,
+    /// Column names from the header row
+    pub columns: Vec<String>,
+    /// Data rows
+    pub rows: Vec<Row>,
+    /// Footnotes inside the table and paragraphs next to it
+    pub notes: Vec<String>,
+}
+
+/// Name of a column without header, 1-based
+///
+fn anonymous(i: usize) -> String {
+    format!("#{}", i + 1)
+}
+
+impl Table {
+    /// Read a `<table>` element, a header row before any data gives the column names, see
+    /// [`is_header_row`]
+    ///
+    pub fn from_element(name: &str, table: ElementRef, opts: &ScrapeOptions) -> Self {
+        // Some entries have an embedded <br> or <br />, only a problem for the fallback
+        //
+        let re = Regex::new(r##"<br\s*/?>"##).unwrap();
+        let sel = Selector::parse("tr").unwrap();
+
+        let mut res = Table {
+            name: opts.clean(name),
+            ..Default::default()
+        };
+        for tr in table.select(&sel) {
+            trace!("tr={tr:?}");
+
+            if res.columns.is_empty() && res.rows.is_empty() && is_header_row(tr) {
+                res.columns = row_cells(tr).iter().map(|c| opts.clean(c)).collect();
+                debug!("columns={:?}", res.columns);
+                continue;
+            }
+
+            // Get the cells from the DOM, whatever markup they have inside and fallback to the
+            // nom grammar on the HTML itself.  Raw mode keeps the HTML as published.
+            //
+            let mut cells = match opts.raw {
+                true => row_html(tr),
+                false => row_cells(tr),
+            };
+            if cells.len() < 2 {
+                let frag = tr.html();
+                let frag = re.replace_all(&frag, "");
+                if let Ok((_, (a, b))) = parse_tr(&frag) {
+                    cells = match opts.raw {
+                        true => vec![a.to_owned(), b.to_owned()],
+                        false => vec![decode_text(a), decode_text(b)],
+                    };
+                }
+            }
+
+            // A single cell spanning the table is a footnote
+            //
+            if cells.len() == 1 {
+                let note = opts.clean(&cells[0]);
+                if !note.is_empty() {
+                    res.notes.push(note);
+                }
+                continue;
+            }
+
+            let cells = cells
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    let col = res.columns.get(i).cloned().unwrap_or_else(|| anonymous(i));
+                    (col, opts.clean(c))
+                })
+                .collect();
+            res.rows.push(Row { cells });
+        }
+
+        // Paragraphs following the table up to the next one, plus the leading ones when it is
+        // the first table of its block, so that each paragraph belongs to a single table.  The
+        // leading ones come before the footnotes to keep the document order.
+        //
+        if let Some(parent) = table.parent().and_then(ElementRef::wrap) {
+            let (mut before, mut after) = (vec![], vec![]);
+            let (mut seen, mut first) = (false, true);
+            for e in parent.children().filter_map(ElementRef::wrap) {
+                match e.value().name() {
+                    "table" if e.id() == table.id() => seen = true,
+                    "table" if seen => break,
+                    "table" => {
+                        first = false;
+                        before.clear();
+                    }
+                    "p" if !seen && !first => (),
+                    "p" => {
+                        let s = match opts.raw {
+                            true => e.inner_html(),
+                            false => opts.clean(&cell_text(e)),
+                        };
+                        if !s.trim().is_empty() {
+                            if seen {
+                                after.push(s)
+                            } else {
+                                before.push(s)
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            res.notes = [before, res.notes, after].concat();
+        }
+        res
+    }
+
+    /// Column with the SAC code, the one whose name starts with "SAC" or the first one
+    ///
+    pub fn sac_column(&self) -> String {
+        self.columns
+            .iter()
+            .find(|c| c.to_uppercase().starts_with("SAC"))
+            .cloned()
+            .unwrap_or_else(|| {
+                self.columns
+                    .first()
+                    .cloned()
+                    .unwrap_or_else(|| anonymous(0))
+            })
+    }
+
+    /// Column with the label, the one about the country/area or the one after the SAC
+    ///
+    pub fn label_column(&self) -> String {
+        let sac = self.sac_column();
+        let found = self.columns.iter().find(|c| {
+            let c = c.to_lowercase();
+            ["country", "area", "label", "name"]
+                .iter()
+                .any(|k| c.contains(k))
+        });
+        match found {
+            Some(c) if *c != sac => c.to_owned(),
+            _ => {
+                let i = self.columns.iter().position(|c| *c == sac).unwrap_or(0);
+                self.columns
+                    .get(i + 1)
+                    .cloned()
+                    .unwrap_or_else(|| anonymous(i + 1))
+            }
+        }
+    }
+
+    /// Build the `Area`, rows without a SAC or a label are skipped
+    ///
+    pub fn to_area(&self) -> Area {
+        let (sac, label) = (self.sac_column(), self.label_column());
+        debug!("{}: sac={} label={}", self.name, sac, label);
+
+        let mut area = Area::new(&self.name);
+        self.notes.iter().for_each(|n| {
+            area.add_note(n);
+        });
+        for row in &self.rows {
+            match (row.get(&sac), row.get(&label)) {
+                (Some(s), Some(l)) if !s.is_empty() && !l.is_empty() => {
+                    area.add(s, l);
+                }
+                _ => warn!("skipping row {:?}", row.cells),
+            }
+        }
+        area
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::Html;
+
+    use super::*;
+
+    fn table(html: &str) -> Table {
+        let doc = Html::parse_fragment(html);
+        let sel = Selector::parse("table").unwrap();
+        let e = doc.select(&sel).next().unwrap();
+        Table::from_element("Asia", e, &ScrapeOptions::default())
+    }
+
+    #[test]
+    fn test_table_columns() {
+        let t = table(
+            "<table><tr><th>SAC(Hexa)</th><th>Country/Geographical Area</th>\
+            <th>Binary Representation</th></tr>\
+            <tr><td>94</td><td>Vietnam</td><td>1001 0100</td></tr></table>",
+        );
+
+        assert_eq!(
+            vec![
+                "SAC(Hexa)",
+                "Country/Geographical Area",
+                "Binary Representation"
+            ],
+            t.columns
+        );
+        assert_eq!(1, t.rows.len());
+        assert_eq!(Some("1001 0100"), t.rows[0].get("Binary Representation"));
+        assert_eq!("SAC(Hexa)", t.sac_column());
+        assert_eq!("Country/Geographical Area", t.label_column());
+    }
+
+    #[test]
+    fn test_table_entities_decoded_once() {
+        let t = table(
+            "<table><tr><th>SAC</th><th>Area</th></tr>\
+            <tr><td>94</td><td>Vietnam &amp;lt;north&amp;gt; &amp; co</td></tr></table>",
+        );
+
+        assert_eq!(Some("Vietnam &lt;north&gt; & co"), t.rows[0].get("Area"));
+    }
+
+    #[test]
+    fn test_table_reordered() {
+        let t = table(
+            "<table><tr><th>Binary</th><th>Country</th><th>SAC</th><th>Since</th></tr>\
+            <tr><td>1001 0100</td><td>Vietnam</td><td>94</td><td>2020</td></tr></table>",
+        );
+
+        assert_eq!(Some("2020"), t.rows[0].get("Since"));
+        let area = t.to_area();
+        assert_eq!("Vietnam", area.get("94").unwrap());
+    }
+
+    #[test]
+    fn test_table_label_mentions_sac() {
+        let t = table(
+            "<table><tr><th>SAC</th><th>Area</th></tr>\
+            <tr><td>F0</td><td>SAC reserved for tests</td></tr></table>",
+        );
+
+        assert_eq!("SAC reserved for tests", t.to_area().get("F0").unwrap());
+    }
+
+    #[test]
+    fn test_table_notes() {
+        let doc = Html::parse_fragment(
+            "<div><p>Codes in <em>italics</em> are reserved.</p><table>\
+            <tr><th>SAC</th><th>Area</th></tr>\
+            <tr><td>94</td><td>Vietnam</td></tr>\
+            <tr><td colspan=\"2\">(*) Temporary</td></tr></table><p> </p></div>",
+        );
+        let sel = Selector::parse("table").unwrap();
+        let e = doc.select(&sel).next().unwrap();
+        let t = Table::from_element("Asia", e, &ScrapeOptions::default());
+
+        assert_eq!(1, t.rows.len());
+        assert_eq!(
+            vec!["Codes in italics are reserved.", "(*) Temporary"],
+            t.notes
+        );
+        assert_eq!(t.notes, t.to_area().notes());
+    }
+
+    #[test]
+    fn test_table_notes_scoped() {
+        let doc = Html::parse_fragment(
+            "<div><p>About Europe</p><table><tr><td>01</td><td>France</td></tr></table>\
+            <p>After Europe</p><p>About Asia</p>\
+            <table><tr><td>94</td><td>Vietnam</td></tr>\
+            <tr><td colspan=\"2\">(*) Temporary</td></tr></table>\
+            <p>After Asia</p><table><tr><td>A0</td><td>Africa</td></tr></table>\
+            <p>After Africa</p></div>",
+        );
+        let sel = Selector::parse("table").unwrap();
+        let e = doc.select(&sel).nth(1).unwrap();
+        let t = Table::from_element("Asia", e, &ScrapeOptions::default());
+        let first = doc.select(&sel).next().unwrap();
+        let europe = Table::from_element("Europe", first, &ScrapeOptions::default());
+
+        assert_eq!(
+            vec!["About Europe", "After Europe", "About Asia"],
+            europe.notes
+        );
+        assert_eq!(vec!["(*) Temporary", "After Asia"], t.notes);
+    }
+
+    #[test]
+    fn test_table_td_header() {
+        let t = table(
+            "<table><tr><td><strong>SAC</strong></td><td><strong>Country</strong></td></tr>\
+            <tr><td>94</td><td><strong>Vietnam</strong></td></tr>\
+            <tr><td><strong>95</strong></td><td><strong>Laos</strong></td></tr></table>",
+        );
+
+        assert_eq!(vec!["SAC", "Country"], t.columns);
+        assert_eq!(2, t.rows.len());
+        assert_eq!(Some("Laos"), t.rows[1].get("Country"));
+        assert_eq!("Vietnam", t.to_area().get("94").unwrap());
+    }
+
+    #[test]
+    fn test_table_no_header() {
+        let t = table("<table><tr><td>94</td><td>Vietnam</td><td>x</td></tr></table>");
+
+        assert!(t.columns.is_empty());
+        assert_eq!(Some("x"), t.rows[0].get("#3"));
+        assert_eq!("Vietnam", t.to_area().get("94").unwrap());
+    }
+}
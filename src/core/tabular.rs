@@ -0,0 +1,408 @@
+//! Module with the exporter shared by the datasets made of simple rows: the categories, the
+//! merged SAC/SIC sites and the sources of a recording
+//!
+//! Each of them only gives its columns and cells through [`Tabular`], every text and
+//! spreadsheet format is then generated from the header and the rows by [`Rows`], CSV with the
+//! same dialect options as for the SAC codes.
+//!
+
+use std::fmt::Display;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use csv::WriterBuilder;
+use html_escape::encode_text;
+use log::trace;
+use quick_xml::events::{BytesDecl, Event};
+use quick_xml::Writer;
+use serde::Serialize;
+
+use crate::core::markdown_output::escape;
+use crate::core::spreadsheet::{ods_from_sheets, xlsx_from_sheets, Sheet};
+use crate::core::{CsvOptions, PAGE, STYLE};
+
+/// A dataset which can be exported as a table
+///
+pub trait Tabular {
+    /// Names of the columns
+    const HEADER: &'static [&'static str];
+    /// Name of the sheet and of the XML root element, e.g. "Categories"
+    const NAME: &'static str;
+    /// XML element for one row, e.g. "category"
+    const ELEMENT: &'static str;
+
+    /// Cells of one row, in the order of `HEADER`
+    fn cells(&self) -> Vec<String>;
+}
+
+/// Plain text, one item per line from its `Display`
+///
+pub fn to_lines<T: Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|i| format!("{i}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// JSON Lines, one item per line
+///
+pub fn to_json_lines<T: Serialize>(items: &[T]) -> Result<String> {
+    trace!("Generating ndjson output…");
+    let lines = items
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(lines.join("\n"))
+}
+
+/// Header and rows of a dataset with what is needed to present it
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Rows {
+    /// Title of Markdown and HTML documents
+    pub title: String,
+    /// Name of the sheet and of the XML root element
+    pub name: String,
+    /// XML element for one row
+    pub element: String,
+    /// Names of the columns
+    pub header: Vec<String>,
+    /// The data, same order as `header`
+    pub rows: Vec<Vec<String>>,
+    /// Retrieval date and last update of the page, for the Markdown and HTML footers
+    pub dates: Option<(DateTime<Utc>, Option<String>)>,
+}
+
+impl Rows {
+    /// Get the rows of `items`, `title` being used for Markdown and HTML
+    ///
+    pub fn new<T: Tabular>(title: &str, items: &[T]) -> Self {
+        Rows {
+            title: title.to_owned(),
+            name: T::NAME.to_owned(),
+            element: T::ELEMENT.to_owned(),
+            header: T::HEADER.iter().map(|h| h.to_string()).collect(),
+            rows: items.iter().map(|i| i.cells()).collect(),
+            dates: None,
+        }
+    }
+
+    /// Say when the data was retrieved and when the page was last updated
+    ///
+    pub fn retrieved(mut self, retrieved: DateTime<Utc>, updated: Option<&str>) -> Self {
+        self.dates = Some((retrieved, updated.map(|u| u.to_owned())));
+        self
+    }
+
+    /// Keep only `columns`, in that order, names are case-insensitive and nothing means all
+    ///
+    pub fn select(mut self, columns: &[String]) -> Result<Self> {
+        if columns.is_empty() {
+            return Ok(self);
+        }
+        let idx = columns
+            .iter()
+            .map(|c| {
+                self.header
+                    .iter()
+                    .position(|h| h.eq_ignore_ascii_case(c))
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "unknown column {}, use {}",
+                            c,
+                            self.header.join(",").to_lowercase()
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let pick = |r: &[String]| -> Vec<String> { idx.iter().map(|&i| r[i].clone()).collect() };
+        self.rows = self.rows.iter().map(|r| pick(r)).collect();
+        self.header = pick(&self.header);
+        Ok(self)
+    }
+
+    /// CSV with the dialect from `opts`, its columns are the ones of the SAC codes and are not
+    /// used, see [`Rows::select`]
+    ///
+    pub fn to_csv(&self, opts: &CsvOptions) -> Result<String> {
+        trace!("Generating {} csv output…", self.name);
+        let mut wtr = WriterBuilder::new()
+            .delimiter(opts.delimiter)
+            .quote_style(opts.quoting.into())
+            .from_writer(vec![]);
+
+        if opts.header {
+            wtr.write_record(&self.header)?;
+        }
+        for row in &self.rows {
+            wtr.write_record(row)?;
+        }
+        Ok(String::from_utf8(wtr.into_inner()?)?)
+    }
+
+    /// XML with one element per row, non-empty cells being attributes named after the columns
+    ///
+    pub fn to_xml(&self) -> Result<String> {
+        trace!("Generating {} xml output…", self.name);
+        let attrs: Vec<String> = self
+            .header
+            .iter()
+            .map(|h| h.to_lowercase().replace(' ', "_"))
+            .collect();
+        let mut wtr = Writer::new_with_indent(vec![], b' ', 2);
+
+        wtr.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+        wtr.create_element(self.name.to_lowercase())
+            .write_inner_content(|w| {
+                for row in &self.rows {
+                    let elem = w.create_element(&self.element).with_attributes(
+                        attrs
+                            .iter()
+                            .zip(row)
+                            .filter(|(_, v)| !v.is_empty())
+                            .map(|(a, v)| (a.as_str(), v.as_str())),
+                    );
+                    elem.write_empty()?;
+                }
+                Ok(())
+            })?;
+        Ok(String::from_utf8(wtr.into_inner())?)
+    }
+
+    /// Markdown with a single table
+    ///
+    pub fn to_markdown(&self) -> String {
+        trace!("Generating {} markdown output…", self.name);
+        let line = |r: &[String]| {
+            let cells: Vec<_> = r.iter().map(|c| escape(c)).collect();
+            format!("| {} |", cells.join(" | "))
+        };
+        let rows = self
+            .rows
+            .iter()
+            .map(|r| line(r))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let dates = match &self.dates {
+            Some((retrieved, updated)) => format!(
+                "Retrieved on {}.{}\n\n",
+                retrieved.format("%Y-%m-%d %H:%M:%S UTC"),
+                updated
+                    .as_ref()
+                    .map(|u| format!(" Page last updated on {}.", u))
+                    .unwrap_or_default()
+            ),
+            None => String::new(),
+        };
+        format!(
+            "# {}\n\n{}{}\n|{}\n{}\n",
+            escape(&self.title),
+            dates,
+            line(&self.header),
+            "---|".repeat(self.header.len()),
+            rows
+        )
+    }
+
+    /// Standalone HTML page with a single table, same style as the SAC report
+    ///
+    pub fn to_html(&self) -> String {
+        trace!("Generating {} html output…", self.name);
+        let line = |r: &[String], tag: &str| {
+            let cells: String = r
+                .iter()
+                .map(|c| format!("<{tag}>{}</{tag}>", encode_text(c)))
+                .collect();
+            format!("<tr>{}</tr>", cells)
+        };
+        let rows = self
+            .rows
+            .iter()
+            .map(|r| line(r, "td"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let footer = match &self.dates {
+            Some((retrieved, updated)) => format!(
+                "<footer>Retrieved on {} from <a href=\"{PAGE}\">ECTL</a>{}.</footer>\n",
+                retrieved.format("%Y-%m-%d %H:%M:%S UTC"),
+                updated
+                    .as_ref()
+                    .map(|u| format!(", page last updated on {}", encode_text(u)))
+                    .unwrap_or_default(),
+            ),
+            None => String::new(),
+        };
+        let title = encode_text(&self.title);
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+            <title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+            <h1>{title}</h1>\n<table>\n\
+            <thead>{}</thead>\n\
+            <tbody>\n{rows}\n</tbody>\n</table>\n\
+            {footer}</body>\n</html>\n",
+            line(&self.header, "th"),
+        )
+    }
+
+    /// A single sheet named after the dataset, header first
+    ///
+    fn sheets(&self) -> Vec<Sheet> {
+        let mut all = vec![self.header.clone()];
+        all.extend(self.rows.iter().cloned());
+        vec![(self.name.to_owned(), all)]
+    }
+
+    /// Excel workbook
+    ///
+    pub fn to_xlsx(&self) -> Result<Vec<u8>> {
+        trace!("Generating {} xlsx output…", self.name);
+        xlsx_from_sheets(&self.sheets())
+    }
+
+    /// OpenDocument spreadsheet
+    ///
+    pub fn to_ods(&self) -> Result<Vec<u8>> {
+        trace!("Generating {} ods output…", self.name);
+        ods_from_sheets(&self.sheets())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::core::Quoting;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        code: u8,
+        name: String,
+    }
+
+    impl Display for Item {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{} = {}", self.code, self.name)
+        }
+    }
+
+    impl Tabular for Item {
+        const HEADER: &'static [&'static str] = &["Code", "Full name"];
+        const NAME: &'static str = "Items";
+        const ELEMENT: &'static str = "item";
+
+        fn cells(&self) -> Vec<String> {
+            vec![self.code.to_string(), self.name.to_owned()]
+        }
+    }
+
+    fn sample() -> Vec<Item> {
+        vec![
+            Item {
+                code: 1,
+                name: "One | first".to_owned(),
+            },
+            Item {
+                code: 2,
+                name: String::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_to_lines_json_lines() {
+        assert_eq!("1 = One | first\n2 = ", to_lines(&sample()));
+
+        let out = to_json_lines(&sample()).unwrap();
+        let i: Item = serde_json::from_str(out.lines().next().unwrap()).unwrap();
+        assert_eq!(sample()[0], i);
+    }
+
+    #[test]
+    fn test_rows_to_csv() {
+        let rows = Rows::new("Items", &sample());
+
+        let out = rows.to_csv(&CsvOptions::default()).unwrap();
+        assert_eq!(
+            "\"Code\",\"Full name\"\n\"1\",\"One | first\"\n\"2\",\"\"\n",
+            out
+        );
+
+        let opts = CsvOptions {
+            delimiter: b';',
+            quoting: Quoting::Necessary,
+            header: false,
+            ..CsvOptions::default()
+        };
+        let out = rows.to_csv(&opts).unwrap();
+        assert_eq!("1;One | first\n2;\n", out);
+    }
+
+    #[test]
+    fn test_rows_select() {
+        let rows = Rows::new("Items", &sample())
+            .select(&["full name".to_owned(), "CODE".to_owned()])
+            .unwrap();
+        assert_eq!(vec!["Full name", "Code"], rows.header);
+        assert_eq!(vec!["One | first", "1"], rows.rows[0]);
+
+        let all = Rows::new("Items", &sample()).select(&[]).unwrap();
+        assert_eq!(2, all.header.len());
+
+        let err = Rows::new("Items", &sample())
+            .select(&["foo".to_owned()])
+            .unwrap_err();
+        assert_eq!("unknown column foo, use code,full name", err.to_string());
+    }
+
+    #[test]
+    fn test_rows_to_xml() {
+        let out = Rows::new("Items", &sample()).to_xml().unwrap();
+
+        assert!(out.contains("<items>"));
+        assert!(out.contains(r#"<item code="1" full_name="One | first"/>"#));
+        assert!(out.contains(r#"<item code="2"/>"#));
+    }
+
+    #[test]
+    fn test_rows_to_markdown_html() {
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let rows = Rows::new("All items", &sample());
+
+        let md = rows.to_markdown();
+        assert!(md.starts_with("# All items\n\n| Code | Full name |\n|---|---|\n"));
+        assert!(md.contains("| 1 | One \\| first |"));
+
+        let md = rows
+            .clone()
+            .retrieved(date, Some("12 March 2023"))
+            .to_markdown();
+        assert!(md.contains(
+            "Retrieved on 2023-04-01 12:00:00 UTC. Page last updated on 12 March 2023.\n"
+        ));
+
+        let html = rows.to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<thead><tr><th>Code</th><th>Full name</th></tr></thead>"));
+        assert!(html.contains("<tr><td>2</td><td></td></tr>"));
+        assert!(!html.contains("<footer>"));
+
+        let html = rows.retrieved(date, None).to_html();
+        assert!(html.contains("<footer>Retrieved on 2023-04-01 12:00:00 UTC"));
+    }
+
+    #[test]
+    fn test_rows_sheets() {
+        let rows = Rows::new("Items", &sample());
+        let s = rows.sheets();
+
+        assert_eq!("Items", s[0].0);
+        assert_eq!(3, s[0].1.len());
+        assert!(!rows.to_xlsx().unwrap().is_empty());
+        assert!(rows.to_ods().unwrap().starts_with(b"PK"));
+    }
+}
@@ -0,0 +1,221 @@
+//! Module generating SAC value tables for Wireshark ASTERIX dissectors
+//!
+//! Both a Lua table for dissectors written in Lua and a C `value_string` array for built-in ones
+//! are available, so that captures show `0x94` with its label, e.g. with `BASE_HEX`:
+//!
+//! ```lua
+//! local sac_vals = dofile("sac_vals.lua")
+//! local f_sac = ProtoField.uint8("asterix.sac", "SAC", base.HEX, sac_vals)
+//! ```
+//!
+//! Ranges are expanded as `value_string` only deals with single values.
+//!
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::trace;
+
+use crate::core::{c_string, tables, Area, PAGE};
+
+/// All values with their label, single codes win over ranges
+///
+fn values(areas: &[Area]) -> BTreeMap<u8, String> {
+    let (sacs, ranges) = tables(areas);
+
+    let mut values = BTreeMap::new();
+    for (v, _, label) in sacs {
+        values.entry(v).or_insert(label);
+    }
+    for (lo, hi, _, label) in ranges {
+        for v in lo..=hi {
+            values.entry(v).or_insert_with(|| label.clone());
+        }
+    }
+    values
+}
+
+/// Escape a string for a Lua literal, control characters are written as decimal escapes
+///
+fn lua_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    out.push_str(&format!("\\{:03}", b));
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Generate a Lua chunk returning a `value_string`-style table, i.e. `{ [0x94] = "label" }`
+///
+pub fn to_wireshark_lua(areas: &[Area], retrieved: DateTime<Utc>) -> Result<String> {
+    trace!("Generating wireshark lua table…");
+    let date = retrieved.format("%Y-%m-%d %H:%M:%S UTC");
+
+    let entries = values(areas)
+        .iter()
+        .map(|(v, label)| format!("    [0x{:02X}] = {},", v, lua_string(label)))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(format!(
+        r##"-- SAC codes from {PAGE}
+--
+-- Retrieved on {date}.
+-- Generated by fetch-sac {}, do not edit.
+
+local sac_vals = {{
+{entries}
+}}
+
+return sac_vals
+"##,
+        env!("CARGO_PKG_VERSION"),
+    ))
+}
+
+/// Generate a C `value_string` array named `sac_vals`, to be included after `<epan/packet.h>`
+///
+pub fn to_wireshark_c(areas: &[Area], retrieved: DateTime<Utc>) -> Result<String> {
+    trace!("Generating wireshark C array…");
+    let date = retrieved.format("%Y-%m-%d %H:%M:%S UTC");
+
+    let entries = values(areas)
+        .iter()
+        .map(|(v, label)| format!("    {{ 0x{:02X}, {} }},", v, c_string(label)))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(format!(
+        r##"/* SAC codes from {PAGE}
+ *
+ * Retrieved on {date}.
+ * Generated by fetch-sac {}, do not edit.
+ */
+
+static const value_string sac_vals[] = {{
+{entries}
+    {{ 0, NULL }}
+}};
+"##,
+        env!("CARGO_PKG_VERSION"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process::Command;
+
+    use chrono::TimeZone;
+    use mlua::{Lua, Table};
+
+    use super::*;
+
+    fn sample() -> Vec<Area> {
+        let mut a = Area::new("Asia");
+        a.add("94", "Vietnam \"south\"");
+        a.add("A0...A3", "Reserved");
+        a.add("A1", "Special");
+        vec![a]
+    }
+
+    #[test]
+    fn test_values() {
+        let v = values(&sample());
+
+        assert_eq!(5, v.len());
+        assert_eq!("Reserved", v[&0xA0]);
+        assert_eq!("Special", v[&0xA1]);
+        assert_eq!("Reserved", v[&0xA3]);
+    }
+
+    #[test]
+    fn test_lua_string() {
+        assert_eq!(r#""a \"b\" \\ c""#, lua_string(r#"a "b" \ c"#));
+        assert_eq!(r#""a\010b""#, lua_string("a\nb"));
+    }
+
+    #[test]
+    fn test_to_wireshark_lua() {
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let code = to_wireshark_lua(&sample(), date).unwrap();
+
+        let lua = Lua::new();
+        let vals: Table = lua.load(&code).eval().unwrap();
+        assert_eq!(5, vals.clone().pairs::<u8, String>().count());
+        assert_eq!("Vietnam \"south\"", vals.get::<_, String>(0x94).unwrap());
+        assert_eq!("Special", vals.get::<_, String>(0xA1).unwrap());
+    }
+
+    #[test]
+    fn test_to_wireshark_c_compiles() {
+        let date = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+        let code = to_wireshark_c(&sample(), date).unwrap();
+        assert!(code.contains("    { 0x94, \"Vietnam \\\"south\\\"\" },"));
+
+        // Compile with a stand-in for the Wireshark type, skipped without a C compiler
+        //
+        let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+        if Command::new(&cc).arg("--version").output().is_err() {
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("sac_vals.c"), code).unwrap();
+        let src = dir.path().join("main.c");
+        fs::write(
+            &src,
+            r#"#include <assert.h>
+#include <stddef.h>
+#include <string.h>
+
+typedef struct {
+    unsigned int value;
+    const char *strptr;
+} value_string;
+
+#include "sac_vals.c"
+
+int main(void)
+{
+    assert(sac_vals[0].value == 0x94);
+    assert(strcmp(sac_vals[2].strptr, "Special") == 0);
+    assert(sac_vals[5].strptr == NULL);
+    return 0;
+}
+"#,
+        )
+        .unwrap();
+
+        let out = Command::new(&cc)
+            .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-o"])
+            .arg(dir.path().join("main"))
+            .arg(&src)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+
+        let out = Command::new(dir.path().join("main")).output().unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+}
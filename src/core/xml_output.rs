@@ -0,0 +1,114 @@
+//! Module dealing with XML output
+//!
+//! The generated document follows the schema in `schemas/fetch-sac.xsd`, available as [`XSD`].
+//!
+
+use anyhow::Result;
+use log::trace;
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::Writer;
+
+use crate::core::{Area, SAC};
+
+/// XML Schema describing the output of [`to_xml`]
+///
+pub const XSD: &str = include_str!("../../schemas/fetch-sac.xsd");
+
+/// Generate an XML document with one `<area>` per area and one `<sac>` per code.
+///
+/// Codes are written as two upper-case hex digits, ranges get `lo`/`hi` attributes instead of
+/// `code`.
+///
+pub fn to_xml(areas: &[Area]) -> Result<String> {
+    trace!("Generating xml output…");
+    let mut wtr = Writer::new_with_indent(vec![], b' ', 2);
+
+    wtr.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    wtr.create_element("areas").write_inner_content(|w| {
+        for area in areas {
+            let name = area.name();
+            w.create_element("area")
+                .with_attribute(("name", name.as_str()))
+                .write_inner_content(|w| {
+                    for (code, label) in area {
+                        let sac = SAC::try_from(code.as_str()).unwrap_or_default();
+                        let elem = w.create_element("sac");
+                        let elem = match &sac {
+                            SAC::Range { lo, hi } => elem
+                                .with_attribute(("lo", format!("{:02X}", lo).as_str()))
+                                .with_attribute(("hi", format!("{:02X}", hi).as_str())),
+                            SAC::Hex(h) => match u8::from_str_radix(h, 16) {
+                                Ok(n) => {
+                                    elem.with_attribute(("code", format!("{:02X}", n).as_str()))
+                                }
+                                Err(_) => elem,
+                            },
+                            SAC::Empty => elem,
+                        };
+                        let elem = match sac.binary() {
+                            Some(bin) => elem.with_attribute(("binary", bin.as_str())),
+                            None => elem,
+                        };
+                        elem.write_text_content(BytesText::new(label))?;
+                    }
+                    Ok(())
+                })?;
+        }
+        Ok(())
+    })?;
+
+    let data = String::from_utf8(wtr.into_inner())?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::process::Command;
+
+    use super::*;
+
+    fn sample() -> Vec<Area> {
+        let mut a = Area::new("Asia & Pacific");
+        a.add("94", "Vietnam");
+        a.add("7", "Short");
+        a.add("A0...C3", "Reserved <internal>");
+        vec![a, Area::new("Empty")]
+    }
+
+    #[test]
+    fn test_to_xml() {
+        let xml = to_xml(&sample()).unwrap();
+
+        assert!(xml.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?>"#));
+        assert!(xml.contains(r#"<area name="Asia &amp; Pacific">"#));
+        assert!(xml.contains(r#"<sac code="94" binary="10010100">Vietnam</sac>"#));
+        assert!(xml.contains(r#"<sac code="07" binary="00000111">Short</sac>"#));
+        assert!(xml.contains(
+            r#"<sac lo="A0" hi="C3" binary="10100000...11000011">Reserved &lt;internal&gt;</sac>"#
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs xmllint from libxml2"]
+    fn test_to_xml_validates_against_xsd() {
+        let mut xsd = tempfile::NamedTempFile::new().unwrap();
+        xsd.write_all(XSD.as_bytes()).unwrap();
+        let mut xml = tempfile::NamedTempFile::new().unwrap();
+        xml.write_all(to_xml(&sample()).unwrap().as_bytes())
+            .unwrap();
+
+        let out = Command::new("xmllint")
+            .arg("--noout")
+            .arg("--schema")
+            .arg(xsd.path())
+            .arg(xml.path())
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+}
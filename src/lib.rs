@@ -0,0 +1,11 @@
+//! Library to fetch the official ASTERIX webpage from ECTL and scrape it in order to get the
+//! official list of SAC codes.
+//!
+//! The `fetch-sac` binary is a thin CLI wrapper around this.
+//!
+
+// Re-export for shorter paths
+//
+pub use crate::core::*;
+
+pub mod core;
@@ -3,26 +3,34 @@
 //!
 //! XXX The fact that I even have to do this is mind-boggling.
 
+use std::fmt::Display;
 use std::fs;
-use std::time::Instant;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
-use chrono::Utc;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
 use clap::Parser;
-use log::{debug, info};
-use reqwest::blocking::get;
+use log::{debug, info, warn};
+use serde::Serialize;
 use stderrlog::LogLevelNum::{Debug, Error, Info, Trace};
 
-use crate::cli::Opts;
-use crate::core::{prepare_data, scrape_data, to_csv};
+use fetch_sac::{
+    bundled, categories_to_protobuf, categories_to_sql, categories_to_sqlite, count,
+    inspect_recording, load_categories, load_file, to_bincode, to_c, to_cbor, to_csv_with, to_html,
+    to_json_envelope_with, to_json_lines, to_lines, to_markdown, to_msgpack, to_ndjson, to_ods,
+    to_protobuf, to_python, to_rust, to_sql, to_sqlite, to_wireshark_c, to_wireshark_lua, to_xlsx,
+    to_xml, Area, Categories, Envelope, Rows, SchemaKind, ScrapeOptions, SicRegistry, Sites,
+};
+#[cfg(feature = "live")]
+use fetch_sac::{fetch_categories, fetch_with};
+
+use crate::cli::{Format, Lang, Opts, SubCommand};
 use crate::version::version;
 
 mod cli;
-mod core;
 mod version;
 
-const PAGE: &str = "https://www.eurocontrol.int/asterix";
-
 fn main() -> Result<()> {
     let opts: Opts = Opts::parse();
 
@@ -54,52 +62,360 @@ fn main() -> Result<()> {
 
     debug!("Debug mode engaged");
 
-    // Fetch the official page
+    // `--sic` replaces the SAC list, it means nothing for the subcommands
     //
-    let now = Instant::now();
-    let doc = get(PAGE)?.text()?;
-    let now = now.elapsed().as_millis();
+    if opts.sic.is_some() && opts.subcmd.is_some() {
+        return Err(anyhow!("--sic can not be used with a subcommand"));
+    }
 
-    info!("Fetch took {} ms", now);
+    // Subcommands not needing the page
+    //
+    if let Some(SubCommand::Schema { kind }) = opts.subcmd {
+        let data = SchemaKind::from(kind).to_json()?;
+        return write_output(opts.output, &text(data));
+    }
+    if let Some(SubCommand::Categories) = opts.subcmd {
+        return categories(&opts);
+    }
+    if let Some(SubCommand::Mirror { dir }) = &opts.subcmd {
+        return mirror(&opts, dir);
+    }
 
-    // Time it
+    // Get the data, either from a file saved earlier, the bundled snapshot or the official page
     //
-    let now = Instant::now();
-    let areas = scrape_data(doc)?;
-    let now = now.elapsed().as_millis();
+    let env = match &opts.input {
+        Some(input) => load_file(input)?,
+        None if opts.bundled => bundled()?,
+        None => live(&opts.scrape_options())?,
+    };
+    let (mut areas, retrieved, updated) = (env.areas, env.retrieved_at, env.page_updated);
+    areas.iter_mut().for_each(|a| a.sort(opts.order));
 
-    info!("Processing took {} ms", now);
+    // Sources of a recording
+    //
+    if let Some(SubCommand::Inspect { file }) = &opts.subcmd {
+        return inspect(&opts, file, &areas);
+    }
 
-    // get everything into `data` as a String, will be either json, csv or plain text
+    // Merged SAC/SIC dataset
     //
-    let data: String = if opts.json {
+    if let Some(registry) = &opts.sic {
+        return sites(&opts, registry, &areas, retrieved, updated.as_deref());
+    }
+
+    // Code generation
+    //
+    if let Some(SubCommand::Codegen { lang }) = opts.subcmd {
+        let data = match lang {
+            Lang::Rust => to_rust(&areas, retrieved)?,
+            Lang::C => to_c(&areas, retrieved)?,
+            Lang::Python => to_python(&areas, retrieved)?,
+            Lang::WiresharkLua => to_wireshark_lua(&areas, retrieved)?,
+            Lang::WiresharkC => to_wireshark_c(&areas, retrieved)?,
+        };
+        return write_output(opts.output, &text(data));
+    }
+
+    // SQLite is not text and goes straight into the database, `-o` is mandatory there
+    //
+    if let (Format::Sqlite, Some(output)) = (opts.format(), &opts.output) {
+        info!("Adding snapshot to {}...", output.to_string_lossy());
+        let id = to_sqlite(&areas, retrieved, updated.as_deref(), output)?;
+        info!("Snapshot #{} added", id);
+        info!("Information retrieved on: {}", retrieved);
+        return Ok(());
+    }
+
+    // get everything into `data`, binary formats are used as-is, text ones from `render()`
+    //
+    let data: Vec<u8> = match opts.format() {
+        Format::Msgpack => to_msgpack(&areas)?,
+        Format::Cbor => to_cbor(&areas)?,
+        Format::Bincode => to_bincode(&areas)?,
+        Format::Protobuf => to_protobuf(&areas, retrieved, updated.as_deref())?,
+        Format::Xlsx => to_xlsx(&areas)?,
+        Format::Ods => to_ods(&areas)?,
+        _ => text(render(&opts, &areas, retrieved, updated.as_deref())?),
+    };
+
+    write_output(opts.output, &data)?;
+
+    info!("Information retrieved on: {}", retrieved);
+    if let Some(updated) = updated {
+        info!("Page last updated on: {}", updated);
+    }
+    Ok(())
+}
+
+/// Fetch the official page and scrape it
+///
+#[cfg(feature = "live")]
+fn live(opts: &ScrapeOptions) -> Result<Envelope> {
+    fetch_with(opts)
+}
+
+/// Built without the scraper, use the bundled snapshot
+///
+#[cfg(not(feature = "live"))]
+fn live(_opts: &ScrapeOptions) -> Result<Envelope> {
+    info!("Built without the live feature, using bundled data");
+    bundled()
+}
+
+/// Fetch the categories, same data sources and formats as for the SAC codes
+///
+fn categories(opts: &Opts) -> Result<()> {
+    let cats = get_categories(opts)?;
+    let (list, retrieved, updated) = (
+        &cats.categories,
+        cats.retrieved_at,
+        cats.page_updated.as_deref(),
+    );
+
+    let rows = Rows::new("ASTERIX categories", list).retrieved(retrieved, updated);
+    let data: Vec<u8> = match opts.format() {
+        Format::JsonEnvelope => text(serde_json::to_string_pretty(&cats)?),
+        Format::Sql => text(categories_to_sql(list, retrieved)?),
+        Format::Protobuf => categories_to_protobuf(&cats)?,
+        Format::Sqlite => match &opts.output {
+            Some(output) => {
+                info!("Adding snapshot to {}...", output.to_string_lossy());
+                let id = categories_to_sqlite(&cats, output)?;
+                info!("Snapshot #{} added", id);
+                return Ok(());
+            }
+            None => return Err(anyhow!("-o is mandatory with -F sqlite")),
+        },
+        fmt => tabular(opts, fmt, rows, list)?,
+    };
+
+    write_output(opts.output.clone(), &data)?;
+
+    info!("Information retrieved on: {}", retrieved);
+    if let Some(updated) = updated {
+        info!("Page last updated on: {}", updated);
+    }
+    Ok(())
+}
+
+/// Check the local SIC registry against `areas` and output both merged
+///
+fn sites(
+    opts: &Opts,
+    registry: &Path,
+    areas: &[Area],
+    retrieved: DateTime<Utc>,
+    updated: Option<&str>,
+) -> Result<()> {
+    let registry = SicRegistry::load(registry)?;
+    let issues = registry.validate(areas);
+    issues.iter().for_each(|i| warn!("{}", i));
+    let sites = registry.merge(areas);
+
+    let rows = Rows::new("SAC/SIC", &sites).retrieved(retrieved, updated);
+    let data: Vec<u8> = match opts.format() {
+        Format::JsonEnvelope => {
+            let mut env = Sites::new(&sites, retrieved)?;
+            env.page_updated = updated.map(str::to_owned);
+            text(serde_json::to_string_pretty(&env)?)
+        }
+        fmt @ (Format::Sql | Format::Sqlite | Format::Protobuf) => {
+            return Err(anyhow!("{:?} is not available with --sic", fmt))
+        }
+        fmt => tabular(opts, fmt, rows, &sites)?,
+    };
+
+    write_output(opts.output.clone(), &data)?;
+
+    info!(
+        "{}, {} in the registry",
+        count(sites.len(), "site"),
+        count(issues.len(), "issue")
+    );
+    Ok(())
+}
+
+/// Read a recording and label its sources from `areas`
+///
+fn inspect(opts: &Opts, file: &Path, areas: &[Area]) -> Result<()> {
+    info!("Reading {}...", file.to_string_lossy());
+    let res = inspect_recording(&fs::read(file)?);
+    res.errors.iter().for_each(|e| warn!("{}", e));
+    res.partial.iter().for_each(|c| {
+        warn!(
+            "CAT{:03}: no UAP, only the first record of each block read",
+            c
+        )
+    });
+    let report = res.report(areas);
+
+    let rows = Rows::new(&format!("Sources in {}", file.to_string_lossy()), &report);
+    let data = match opts.format() {
+        Format::JsonEnvelope => text(serde_json::to_string_pretty(&serde_json::json!({
+            "file": file,
+            "bytes": res.bytes,
+            "blocks": res.blocks,
+            "records": res.records,
+            "partial": res.partial,
+            "errors": res.errors,
+            "sources": report,
+        }))?),
+        fmt @ (Format::Sql | Format::Sqlite | Format::Protobuf) => {
+            return Err(anyhow!("{:?} is not available for inspect", fmt))
+        }
+        fmt => tabular(opts, fmt, rows, &report)?,
+    };
+
+    write_output(opts.output.clone(), &data)?;
+
+    info!(
+        "{}, {}, {} from {}",
+        count(res.bytes, "byte"),
+        count(res.blocks, "block"),
+        count(res.records, "record"),
+        count(report.len(), "source")
+    );
+    Ok(())
+}
+
+/// Get the categories, either from a file or from the official page
+///
+fn get_categories(opts: &Opts) -> Result<Categories> {
+    if opts.bundled {
+        return Err(anyhow!("no bundled data for categories"));
+    }
+    match &opts.input {
+        Some(input) => load_categories(input, &opts.scrape_options()),
+        None => live_categories(&opts.scrape_options()),
+    }
+}
+
+/// Download the specifications of all categories into `dir`
+///
+#[cfg(feature = "live")]
+fn mirror(opts: &Opts, dir: &Path) -> Result<()> {
+    let cats = get_categories(opts)?;
+    let (manifest, report) = fetch_sac::mirror(&cats.categories, dir)?;
+
+    info!(
+        "{} documents in {}: {} downloaded, {} unchanged, {} failed",
+        manifest.entries.len(),
+        dir.to_string_lossy(),
+        report.downloaded,
+        report.unchanged,
+        report.failed.len()
+    );
+    match report.failed.len() {
+        0 => Ok(()),
+        n => Err(anyhow!("{} documents could not be downloaded", n)),
+    }
+}
+
+/// Built without the scraper, there is nothing to download with
+///
+#[cfg(not(feature = "live"))]
+fn mirror(_opts: &Opts, _dir: &Path) -> Result<()> {
+    Err(anyhow!(
+        "built without the live feature, mirror is not available"
+    ))
+}
+
+/// Fetch the official page and scrape the categories
+///
+#[cfg(feature = "live")]
+fn live_categories(opts: &ScrapeOptions) -> Result<Categories> {
+    fetch_categories(opts)
+}
+
+/// Built without the scraper and there is no bundled data for categories
+///
+#[cfg(not(feature = "live"))]
+fn live_categories(_opts: &ScrapeOptions) -> Result<Categories> {
+    Err(anyhow!(
+        "built without the live feature, use -i with a saved copy of the page"
+    ))
+}
+
+/// Formats shared by the categories, the sites and inspect, from their rows or the items
+/// themselves
+///
+fn tabular<T>(opts: &Opts, fmt: Format, rows: Rows, items: &[T]) -> Result<Vec<u8>>
+where
+    T: Display + Serialize,
+{
+    Ok(match fmt {
+        Format::Text => text(to_lines(items)),
+        Format::Csv => {
+            let columns = opts.columns.clone().unwrap_or_default();
+            text(rows.select(&columns)?.to_csv(&opts.csv_dialect())?)
+        }
+        Format::Json => text(serde_json::to_string(items)?),
+        Format::Ndjson => text(to_json_lines(items)?),
+        Format::Xml => text(rows.to_xml()?),
+        Format::Markdown => text(rows.to_markdown()),
+        Format::Html => text(rows.to_html()),
+        Format::Msgpack => to_msgpack(items)?,
+        Format::Cbor => to_cbor(items)?,
+        Format::Bincode => to_bincode(items)?,
+        Format::Xlsx => rows.to_xlsx()?,
+        Format::Ods => rows.to_ods()?,
+        _ => return Err(anyhow!("{:?} is not available here", fmt)),
+    })
+}
+
+/// Generate all the text formats
+///
+fn render(
+    opts: &Opts,
+    areas: &[Area],
+    retrieved: DateTime<Utc>,
+    updated: Option<&str>,
+) -> Result<String> {
+    let fmt = opts.format();
+    Ok(match fmt {
         // Info json directly
         //
-        serde_json::to_string(&areas)?
-    } else if opts.csv {
-        // Flatten the different areas into one
-        //
-        to_csv(prepare_data(&areas)?)?
-    } else {
+        Format::Json => serde_json::to_string(&areas)?,
+        Format::JsonEnvelope => {
+            let mut env = Envelope::new(areas, retrieved)?;
+            env.page_updated = updated.map(|u| u.to_owned());
+            to_json_envelope_with(&env)?
+        }
+        Format::Ndjson => to_ndjson(areas)?,
+        Format::Csv => to_csv_with(areas, &opts.csv_options()?)?,
+        Format::Xml => to_xml(areas)?,
+        Format::Markdown => to_markdown(areas, retrieved, updated)?,
+        Format::Html => to_html(areas, retrieved, updated)?,
+        Format::Sql => to_sql(areas, retrieved, updated)?,
         // Just plain text,  prettier than just `dbg!()`
         //
-        areas
+        Format::Text => areas
             .iter()
             .map(|a| format!("{a}"))
             .collect::<Vec<_>>()
-            .join("\n")
-    };
+            .join("\n"),
+        _ => unreachable!("{:?} is not a text format", fmt),
+    })
+}
 
-    // Write output
-    //
-    match opts.output {
+/// Text output always ends with a newline
+///
+fn text(mut data: String) -> Vec<u8> {
+    if !data.ends_with('\n') {
+        data.push('\n');
+    }
+    data.into_bytes()
+}
+
+/// Write output, either in the given file or stdout
+///
+fn write_output(output: Option<PathBuf>, data: &[u8]) -> Result<()> {
+    match output {
         Some(output) => {
             info!("Writing {}...", output.to_string_lossy());
             fs::write(output, data)?
         }
-        _ => println!("{}", data),
+        _ => io::stdout().write_all(data)?,
     }
-
-    info!("Information retrieved on: {}", Utc::now());
     Ok(())
 }
@@ -1,13 +1,38 @@
 use std::fs;
+use std::path::{Path, PathBuf};
 
 use assert_cmd::Command;
+use chrono::Utc;
+
+use fetch_sac::{to_protobuf, Area};
 
 const BIN: &str = "fetch-sac";
 
+/// Save a small dataset to be used with `-i`
+///
+fn sample_input(dir: &Path) -> PathBuf {
+    let mut a = Area::new("Asia");
+    a.add("94", "Vietnam");
+    let input = dir.join("sac.pb");
+    fs::write(&input, to_protobuf(&[a], Utc::now(), None).unwrap()).unwrap();
+    input
+}
+
+/// Whether `data/sac.json` still has to be refreshed from the page
+///
+fn bundled_empty() -> bool {
+    include_str!("../data/sac.json").contains("\"areas\": []")
+}
+
 #[test]
 fn test_empty_args() {
+    // Without the live feature, the bundled data is used
+    //
     let mut cmd = Command::cargo_bin(BIN).unwrap();
-    cmd.assert().success();
+    match bundled_empty() && cfg!(not(feature = "live")) {
+        true => cmd.assert().failure(),
+        false => cmd.assert().success(),
+    };
 }
 
 #[test]
@@ -24,19 +49,483 @@ fn test_version() {
 
 #[test]
 fn test_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = sample_input(dir.path());
+
     let mut cmd = Command::cargo_bin(BIN).unwrap();
-    cmd.arg("-J").assert().success();
+    cmd.arg("-i").arg(&input).arg("-J").assert().success();
 }
 
 #[test]
 fn test_csv() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = sample_input(dir.path());
+
     let mut cmd = Command::cargo_bin(BIN).unwrap();
-    cmd.arg("-C").assert().success();
+    cmd.arg("-i").arg(&input).arg("-C").assert().success();
 }
 
 #[test]
 fn test_output_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = sample_input(dir.path());
+    let output = dir.path().join("foo.txt");
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&output).unwrap().contains("Vietnam"));
+}
+
+#[test]
+fn test_format_xml() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = sample_input(dir.path());
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .arg("-F")
+        .arg("xml")
+        .assert()
+        .success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(out.contains("Vietnam"));
+}
+
+#[test]
+fn test_format_conflict() {
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.arg("-C").arg("-F").arg("json").assert().failure();
+}
+
+#[test]
+fn test_format_sqlite_needs_output() {
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.arg("-F").arg("sqlite").assert().failure();
+}
+
+#[test]
+fn test_schema() {
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd.arg("schema").arg("sac").assert().success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(out.contains(r#""title": "SAC""#));
+}
+
+#[test]
+fn test_input_protobuf() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = sample_input(dir.path());
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd.arg("-i").arg(&input).arg("-J").assert().success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(out.contains("Vietnam"));
+}
+
+#[test]
+fn test_input_unknown() {
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.arg("-i").arg("Cargo.toml").assert().failure();
+}
+
+#[test]
+fn test_input_bad_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("sac.csv");
+    fs::write(&input, "Region,SAC,Label\nAsia,X...Y,Nowhere\n").unwrap();
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.arg("-i")
+        .arg(&input)
+        .arg("-F")
+        .arg("xml")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_csv_options() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = sample_input(dir.path());
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .arg("-C")
+        .args(["--delimiter", "tab", "--quote", "never", "--no-header"])
+        .args(["--columns", "slug,decimal,label"])
+        .assert()
+        .success();
+    assert_eq!(b"asia\t148\tVietnam\n", out.get_output().stdout.as_slice());
+}
+
+#[test]
+fn test_csv_bad_column() {
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.args(["-C", "--columns", "foo"]).assert().failure();
+}
+
+#[test]
+fn test_input_csv_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = sample_input(dir.path());
+    let csv = dir.path().join("sac.csv");
+    let json = dir.path().join("sac.json");
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.arg("-i").arg(&input).arg("-C").arg("-o").arg(&csv);
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.arg("-i").arg(&csv).arg("-J").arg("-o").arg(&json);
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd.arg("-i").arg(&json).assert().success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert_eq!("Asia\n  94 = Vietnam\n", out);
+}
+
+#[test]
+fn test_codegen_rust() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = sample_input(dir.path());
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .args(["codegen", "rust"])
+        .assert()
+        .success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(out.contains("pub fn lookup"));
+    assert!(out.contains("(0x94, \"Asia\", \"Vietnam\"),"));
+}
+
+#[test]
+fn test_codegen_c_python() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = sample_input(dir.path());
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .args(["codegen", "c"])
+        .assert()
+        .success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(out.contains("SAC_ASIA_94 = 0x94,"));
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .args(["codegen", "python"])
+        .assert()
+        .success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(out.contains("0x94: (\"Asia\", \"Vietnam\"),"));
+}
+
+#[test]
+fn test_codegen_wireshark() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = sample_input(dir.path());
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .args(["codegen", "wireshark-lua"])
+        .assert()
+        .success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(out.contains("    [0x94] = \"Vietnam\","));
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .args(["codegen", "wireshark-c"])
+        .assert()
+        .success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(out.contains("    { 0x94, \"Vietnam\" },"));
+}
+
+#[test]
+fn test_bundled() {
+    // Only usable once `data/sac.json` has been refreshed from the page
+    //
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd.args(["-b", "-F", "json-envelope"]).assert();
+    if bundled_empty() {
+        let out = out.failure();
+        let err = String::from_utf8_lossy(&out.get_output().stderr).to_string();
+        assert!(err.contains("bundled data/sac.json is empty"));
+    } else {
+        let out = out.success();
+        let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+        assert!(out.contains("\"schema_version\": 1"));
+    }
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.args(["-b", "-i", "sac.json"]).assert().failure();
+}
+
+#[test]
+fn test_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("sac.csv");
+    std::fs::write(
+        &input,
+        "Region,SAC,Label\nAsia,94,Vietnam\nAsia,01,Zulu\nAsia,A0,Alpha\n",
+    )
+    .unwrap();
+
+    for (order, res) in [
+        ("page", "Asia\n  94 = Vietnam\n  01 = Zulu\n  A0 = Alpha\n"),
+        ("hex", "Asia\n  01 = Zulu\n  94 = Vietnam\n  A0 = Alpha\n"),
+        ("label", "Asia\n  A0 = Alpha\n  94 = Vietnam\n  01 = Zulu\n"),
+    ] {
+        let mut cmd = Command::cargo_bin(BIN).unwrap();
+        let out = cmd
+            .arg("-i")
+            .arg(&input)
+            .args(["--order", order])
+            .assert()
+            .success();
+        let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+        assert_eq!(res, out, "--order {}", order);
+    }
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.arg("-i").arg(&input).args(["--order", "foo"]);
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_page_updated() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("sac.json");
+    std::fs::write(
+        &input,
+        r#"{"schema_version":1,"source_url":"https://www.eurocontrol.int/asterix",
+        "retrieved_at":"2023-04-01T12:00:00Z","page_updated":"12 March 2023","hash":"",
+        "areas":[{"label":"Asia","list":{"94":"Vietnam"},"notes":["Reserved codes"]}]}"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .args(["-F", "markdown"])
+        .assert()
+        .success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(out.contains("Page last updated on 12 March 2023."));
+    assert!(out.contains("> Reserved codes"));
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .args(["-F", "json-envelope"])
+        .assert()
+        .success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(out.contains("\"page_updated\": \"12 March 2023\""));
+}
+
+#[test]
+fn test_categories() {
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .args(["-i", "testdata/asterix.html", "-F", "csv", "categories"])
+        .assert()
+        .success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(out.starts_with(r#""Category","Name","Title","Edition","Date","Links""#));
+    assert!(out.contains(r#""62","CAT062","SDPS Track Messages","1.19","12 March 2023""#));
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .args(["-i", "testdata/asterix.html", "-F", "sql", "categories"])
+        .assert()
+        .success();
+    let out = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(out.contains("DELETE FROM asterix_categories WHERE number NOT IN (1, 21, 62);"));
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .args(["-i", "testdata/asterix.html", "-C", "--delimiter", ";"])
+        .args(["--quote", "necessary", "--columns", "name,edition"])
+        .arg("categories")
+        .assert()
+        .success();
+    assert_eq!(
+        b"Name;Edition\nCAT001;1.4\nCAT021;2.6\nCAT062;1.19\n",
+        out.get_output().stdout.as_slice()
+    );
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.args(["-i", "testdata/asterix.html", "-C", "--columns", "region"])
+        .arg("categories")
+        .assert()
+        .failure();
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("sac.db");
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.args(["-i", "testdata/asterix.html", "-F", "sqlite", "-o"])
+        .arg(&db)
+        .arg("categories")
+        .assert()
+        .success();
+    assert!(db.exists());
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.args(["-b", "categories"]).assert().failure();
+}
+
+#[cfg(feature = "live")]
+#[test]
+fn test_mirror() {
+    use tiny_http::{Response, Server};
+
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", server.server_addr().to_ip().unwrap());
+    let handle = std::thread::spawn(move || {
+        let req = server.incoming_requests().next().unwrap();
+        req.respond(Response::from_data(b"%PDF-1.4".to_vec()))
+            .unwrap();
+    });
+
+    let dir = tempfile::tempdir().unwrap();
+    let page = dir.path().join("asterix.html");
+    fs::write(
+        &page,
+        format!(
+            "<html><body><ul><li><a href=\"{base}/cat062.pdf\">Cat 062 - SDPS Track Messages</a> \
+            Edition 1.19</li></ul></body></html>"
+        ),
+    )
+    .unwrap();
+    let specs = dir.path().join("specs");
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    cmd.arg("-i")
+        .arg(&page)
+        .arg("mirror")
+        .arg(&specs)
+        .assert()
+        .success();
+    handle.join().unwrap();
+
+    assert_eq!(
+        b"%PDF-1.4".to_vec(),
+        fs::read(specs.join("cat062/cat062.pdf")).unwrap()
+    );
+    let manifest = fs::read_to_string(specs.join("manifest.json")).unwrap();
+    assert!(manifest.contains("\"edition\": \"1.19\""));
+    assert!(manifest.contains("\"size\": 8"));
+}
+
+#[test]
+fn test_sic_registry() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = sample_input(dir.path());
+    let registry = dir.path().join("sic.toml");
+    fs::write(
+        &registry,
+        "[[site]]\nsac = \"94\"\nsic = 1\nname = \"Noi Bai\"\nsensor = \"PSR/SSR\"\n\n\
+        [[site]]\nsac = \"12\"\nsic = 3\nname = \"Nowhere\"\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .arg("--sic")
+        .arg(&registry)
+        .arg("-C")
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    let stderr = String::from_utf8_lossy(&out.get_output().stderr).to_string();
+    assert!(stdout.contains(r#""Asia","94","01","Vietnam","Noi Bai","PSR/SSR","","148/1""#));
+    assert!(stderr.contains("Nowhere: 12/03 has an unknown SAC"));
+
+    // Envelope like the other outputs
+    //
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .arg("--sic")
+        .arg(&registry)
+        .args(["-F", "json-envelope"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    assert!(stdout.contains("\"schema_version\": 1"));
+    assert!(stdout.contains("\"hash\": \"sha256:"));
+
+    // Not with a subcommand
+    //
+    let mut cmd = Command::cargo_bin(BIN).unwrap();
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .arg("--sic")
+        .arg(&registry)
+        .args(["codegen", "rust"])
+        .assert()
+        .failure();
+    let stderr = String::from_utf8_lossy(&out.get_output().stderr).to_string();
+    assert!(stderr.contains("--sic can not be used with a subcommand"));
+}
+
+#[test]
+fn test_inspect() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = sample_input(dir.path());
+    let recording = dir.path().join("rec.ast");
+    // One CAT048 block with two records from 94/01, one CAT062 block with a record from 12/03
+    //
+    fs::write(
+        &recording,
+        [
+            &[48, 0x00, 0x09, 0x80, 0x94, 0x01, 0x80, 0x94, 0x01][..],
+            &[62, 0x00, 0x06, 0x80, 0x12, 0x03][..],
+        ]
+        .concat(),
+    )
+    .unwrap();
+
     let mut cmd = Command::cargo_bin(BIN).unwrap();
-    cmd.arg("-o").arg("foo.txt").assert().success();
-    fs::remove_file("foo.txt").unwrap()
+    let out = cmd
+        .arg("-i")
+        .arg(&input)
+        .arg("inspect")
+        .arg(&recording)
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&out.get_output().stdout).to_string();
+    let stderr = String::from_utf8_lossy(&out.get_output().stderr).to_string();
+    assert!(stdout.contains("CAT048 94/01 (148/1): 2 records in 1 block, Asia / Vietnam"));
+    assert!(stdout.contains("CAT062 12/03 (18/3): 1 record in 1 block, unknown SAC"));
+    assert!(stderr.contains("15 bytes, 2 blocks, 3 records from 2 sources"));
 }